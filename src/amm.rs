@@ -0,0 +1,85 @@
+//! Math for constant-product automated market makers.
+//!
+//! Intermediate products are computed in the U192 domain so that no
+//! precision is lost before the final rounding.
+
+use crate::{MathError, RoundingMode, U192};
+use solana_program::program_error::ProgramError;
+use std::convert::TryFrom;
+
+/// Output amount of a constant-product swap,
+/// reserve_out * amount_in / (reserve_in + amount_in)
+pub fn constant_product_out(
+    reserve_in: u64,
+    reserve_out: u64,
+    amount_in: u64,
+    mode: RoundingMode,
+) -> Result<u64, ProgramError> {
+    let numerator = U192::from(reserve_out) * U192::from(amount_in);
+    let denominator = U192::from(reserve_in) + U192::from(amount_in);
+    let amount_out = numerator.try_div_rounding(denominator, mode)?;
+    Ok(u64::try_from(amount_out).map_err(|_| MathError::UnableToRoundU64)?)
+}
+
+/// Check the constant-product invariant new_in * new_out >= reserve_in * reserve_out
+pub fn verify_constant_product(
+    reserve_in: u64,
+    reserve_out: u64,
+    new_in: u64,
+    new_out: u64,
+) -> Result<bool, ProgramError> {
+    let invariant = U192::from(reserve_in)
+        .checked_mul(U192::from(reserve_out))
+        .ok_or(MathError::MulOverflow)?;
+    let new_invariant = U192::from(new_in)
+        .checked_mul(U192::from(new_out))
+        .ok_or(MathError::MulOverflow)?;
+    Ok(new_invariant >= invariant)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_constant_product_out() {
+        let (reserve_in, reserve_out, amount_in) = (1_000_000u64, 2_000_003u64, 1_000u64);
+        let numerator = reserve_out as u128 * amount_in as u128;
+        let denominator = reserve_in as u128 + amount_in as u128;
+        let floor = (numerator / denominator) as u64;
+        assert_ne!(numerator % denominator, 0);
+
+        assert_eq!(
+            constant_product_out(reserve_in, reserve_out, amount_in, RoundingMode::Floor).unwrap(),
+            floor
+        );
+        assert_eq!(
+            constant_product_out(reserve_in, reserve_out, amount_in, RoundingMode::Ceil).unwrap(),
+            floor + 1
+        );
+        assert_eq!(
+            constant_product_out(reserve_in, reserve_out, amount_in, RoundingMode::HalfUp).unwrap(),
+            ((numerator + denominator / 2) / denominator) as u64
+        );
+
+        assert_eq!(
+            constant_product_out(u64::MAX, u64::MAX, u64::MAX, RoundingMode::Floor).unwrap(),
+            ((u64::MAX as u128 * u64::MAX as u128) / (2 * u64::MAX as u128)) as u64
+        );
+        assert_eq!(
+            constant_product_out(0, 100, 0, RoundingMode::Floor),
+            Err(MathError::DividedByZero.into())
+        );
+    }
+
+    #[test]
+    fn test_verify_constant_product() {
+        assert!(verify_constant_product(1_000, 2_000, 1_100, 1_819).unwrap());
+        assert!(!verify_constant_product(1_000, 2_000, 1_100, 1_818).unwrap());
+
+        let max = u64::MAX;
+        assert!(verify_constant_product(max, max, max, max).unwrap());
+        assert!(!verify_constant_product(max, max, max, max - 1).unwrap());
+        assert!(verify_constant_product(max - 1, max, max, max - 1).unwrap());
+    }
+}
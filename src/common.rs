@@ -37,3 +37,29 @@ pub trait TryMul<RHS>: Sized {
     /// Multiply
     fn try_mul(self, rhs: RHS) -> Result<Self, ProgramError>;
 }
+
+/// Rounding applied to results that are not exact
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RoundingMode {
+    /// Round down
+    Floor,
+    /// Round up
+    Ceil,
+    /// Round to nearest, ties round up
+    HalfUp,
+    /// Round to nearest, ties round to the even neighbour
+    HalfEven,
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_constants_consistent() {
+        assert_eq!(WAD, 10u64.pow(SCALE as u32));
+        assert_eq!(HALF_WAD, WAD / 2);
+        assert_eq!(PERCENT_SCALER, WAD / 100);
+        assert_eq!(BIPS_SCALER, WAD / 10_000);
+    }
+}
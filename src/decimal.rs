@@ -11,14 +11,15 @@
 #![allow(clippy::assign_op_pattern)]
 #![allow(clippy::ptr_offset_with_cast)]
 #![allow(clippy::manual_range_contains)]
+#![allow(clippy::manual_is_multiple_of)]
 use crate::{
-    MathError, Rate, TryAdd, TryDiv, TryMul, TrySub, BIPS_SCALER, HALF_WAD, PERCENT_SCALER, SCALE,
-    WAD,
+    MathError, Rate, RoundingMode, TryAdd, TryDiv, TryMul, TrySub, BIPS_SCALER, HALF_WAD,
+    PERCENT_SCALER, SCALE, WAD,
 };
 use arrayref::{array_mut_ref, array_ref};
 use solana_program::program_error::ProgramError;
 use solana_program::program_pack::{Pack, Sealed};
-use std::{convert::TryFrom, fmt};
+use std::{cmp::Ordering, convert::TryFrom, fmt, str::FromStr};
 use uint::construct_uint;
 
 // U192 with 192 bits consisting of 3 x 64-bit words
@@ -26,6 +27,93 @@ construct_uint! {
     pub struct U192(3);
 }
 
+// U256 with 256 bits consisting of 4 x 64-bit words
+construct_uint! {
+    pub struct U256(4);
+}
+
+impl From<U192> for U256 {
+    fn from(val: U192) -> Self {
+        let U192([a, b, c]) = val;
+        U256([a, b, c, 0])
+    }
+}
+
+macro_rules! impl_div_rounding {
+    ($uint:ident) => {
+        impl $uint {
+            // Divide with the given rounding
+            pub(crate) fn try_div_rounding(
+                self,
+                rhs: Self,
+                mode: RoundingMode,
+            ) -> Result<Self, ProgramError> {
+                if rhs.is_zero() {
+                    return Err(MathError::DividedByZero.into());
+                }
+                let (quotient, remainder) = self.div_mod(rhs);
+                let round_up = match mode {
+                    RoundingMode::Floor => false,
+                    RoundingMode::Ceil => !remainder.is_zero(),
+                    RoundingMode::HalfUp => remainder >= rhs - remainder,
+                    RoundingMode::HalfEven => match remainder.cmp(&(rhs - remainder)) {
+                        Ordering::Less => false,
+                        Ordering::Equal => quotient.bit(0),
+                        Ordering::Greater => true,
+                    },
+                };
+                if round_up {
+                    Ok(quotient
+                        .checked_add(Self::one())
+                        .ok_or(MathError::AddOverflow)?)
+                } else {
+                    Ok(quotient)
+                }
+            }
+        }
+    };
+}
+
+impl_div_rounding!(U192);
+impl_div_rounding!(U256);
+
+impl U256 {
+    // Narrow to U192, erroring if the value does not fit
+    fn try_narrow(self) -> Result<U192, ProgramError> {
+        let U256([a, b, c, d]) = self;
+        if d != 0 {
+            return Err(MathError::MulOverflow.into());
+        }
+        Ok(U192([a, b, c]))
+    }
+
+    // Floor of the square root using Newton's method
+    fn integer_sqrt(self) -> Self {
+        if self.is_zero() {
+            return self;
+        }
+        let mut x = Self::one() << (self.bits() / 2 + 1);
+        loop {
+            let y = (x + self / x) >> 1;
+            if y >= x {
+                return x;
+            }
+            x = y;
+        }
+    }
+}
+
+// Powers of ten that fit in u128
+const POW10: [u128; 39] = {
+    let mut table = [1u128; 39];
+    let mut i = 1;
+    while i < table.len() {
+        table[i] = table[i - 1] * 10;
+        i += 1;
+    }
+    table
+};
+
 /// Large decimal values, precise to 18 digits
 #[derive(Clone, Copy, Debug, Default, PartialEq, PartialOrd, Eq, Ord)]
 pub struct Decimal(pub U192);
@@ -41,6 +129,16 @@ impl Decimal {
         Self(U192::zero())
     }
 
+    /// Euler's number e, truncated to 18 decimal places
+    pub fn ln_e() -> Self {
+        Self::from_scaled_val(2_718_281_828_459_045_235)
+    }
+
+    /// Pi, truncated to 18 decimal places
+    pub fn pi() -> Self {
+        Self::from_scaled_val(3_141_592_653_589_793_238)
+    }
+
     // OPTIMIZE: use const slice when fixed in BPF toolchain
     fn wad() -> U192 {
         U192::from(WAD)
@@ -51,6 +149,114 @@ impl Decimal {
         U192::from(HALF_WAD)
     }
 
+    // 10^exp, erroring if it does not fit in U192
+    fn try_pow10(mut exp: u32) -> Result<U192, ProgramError> {
+        let max_exp = POW10.len() as u32 - 1;
+        let mut pow = U192::one();
+        while exp > max_exp {
+            pow = pow
+                .checked_mul(U192::from(POW10[max_exp as usize]))
+                .ok_or(MathError::MulOverflow)?;
+            exp -= max_exp;
+        }
+        Ok(pow
+            .checked_mul(U192::from(POW10[exp as usize]))
+            .ok_or(MathError::MulOverflow)?)
+    }
+
+    // ln(2) rounded to 18 decimal places
+    fn ln2() -> Self {
+        Self::from_scaled_val(693_147_180_559_945_309)
+    }
+
+    // |self - other|
+    fn abs_diff(&self, other: Self) -> Self {
+        Self(if self.0 > other.0 {
+            self.0 - other.0
+        } else {
+            other.0 - self.0
+        })
+    }
+
+    // Split a non-zero self into m * 2^k with m in [1, 2)
+    fn split_log2(&self) -> (Self, i64) {
+        let wad = Self::wad();
+        let mut m = self.0;
+        let mut k: i64 = 0;
+        while m >= wad << 1 {
+            m = m >> 1;
+            k += 1;
+        }
+        while m < wad {
+            m = m << 1;
+            k -= 1;
+        }
+        (Self(m), k)
+    }
+
+    // Natural logarithm as (magnitude, is_negative)
+    fn try_ln_signed(&self) -> Result<(Self, bool), ProgramError> {
+        if self.0.is_zero() {
+            return Err(MathError::NegativeValue.into());
+        }
+
+        // ln(m) = 2 * (z + z^3/3 + z^5/5 + ...) where z = (m - 1) / (m + 1)
+        let (m, k) = self.split_log2();
+        let z = m.try_sub(Self::one())?.try_div(m.try_add(Self::one())?)?;
+        let z_squared = z.try_mul(z)?;
+        let mut term = z;
+        let mut sum = Self::zero();
+        let mut n = 1u64;
+        while !term.0.is_zero() {
+            sum = sum.try_add(term.try_div(n)?)?;
+            term = term.try_mul(z_squared)?;
+            n += 2;
+        }
+        let ln_m = sum.try_mul(2u64)?;
+
+        let k_ln2 = Self::ln2().try_mul(k.unsigned_abs())?;
+        if k >= 0 {
+            Ok((ln_m.try_add(k_ln2)?, false))
+        } else {
+            // ln(m) < ln(2) <= |k| * ln(2)
+            Ok((k_ln2.try_sub(ln_m)?, true))
+        }
+    }
+
+    // e^self, or e^-self when negative is set
+    fn try_exp_signed(&self, negative: bool) -> Result<Self, ProgramError> {
+        // Reduce to self = k * ln(2) + r with r in [0, ln(2))
+        let k = self.0 / Self::ln2().0;
+        if k >= U192::from(192u64) {
+            return if negative {
+                Ok(Self::zero())
+            } else {
+                Err(MathError::MulOverflow.into())
+            };
+        }
+        let k = k.as_u64();
+        let r = self.try_sub(Self::ln2().try_mul(k)?)?;
+
+        // e^r = 1 + r + r^2/2! + r^3/3! + ...
+        let mut term = Self::one();
+        let mut sum = Self::one();
+        let mut n = 1u64;
+        while !term.0.is_zero() {
+            term = term.try_mul(r)?.try_div(n)?;
+            sum = sum.try_add(term)?;
+            n += 1;
+        }
+
+        let k = k as usize;
+        if negative {
+            Ok(Self(sum.try_recip()?.0 >> k))
+        } else if sum.0.bits() + k > 192 {
+            Err(MathError::MulOverflow.into())
+        } else {
+            Ok(Self(sum.0 << k))
+        }
+    }
+
     /// Create scaled decimal from percent value
     pub fn from_percent(percent: u8) -> Self {
         Self(U192::from(percent as u64 * PERCENT_SCALER))
@@ -70,6 +276,17 @@ impl Decimal {
         Self(U192::from(scaled_val))
     }
 
+    /// Create decimal from a 16 or 24 byte little-endian scaled value
+    pub fn try_from_scaled_bytes(bytes: &[u8]) -> Result<Self, ProgramError> {
+        match bytes.len() {
+            16 => Ok(Self::from_scaled_val(u128::from_le_bytes(*array_ref![
+                bytes, 0, 16
+            ]))),
+            24 => Ok(Self(U192::from_little_endian(bytes))),
+            _ => Err(ProgramError::InvalidArgument),
+        }
+    }
+
     /// Round scaled decimal to u64
     pub fn try_round_u64(&self) -> Result<u64, ProgramError> {
         let rounded_val = Self::half_wad()
@@ -100,133 +317,3449 @@ impl Decimal {
             .ok_or(MathError::DividedByZero)?;
         Ok(u64::try_from(ceil_val).map_err(|_| MathError::UnableToRoundU64)?)
     }
-}
 
-impl fmt::Display for Decimal {
-    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        let mut scaled_val = self.0.to_string();
-        if scaled_val.len() <= SCALE {
-            scaled_val.insert_str(0, &vec!["0"; SCALE - scaled_val.len()].join(""));
-            scaled_val.insert_str(0, "0.");
+    /// Round scaled decimal to the nearest multiple of a u64
+    pub fn try_round_nearest_u64(&self, multiple: u64) -> Result<u64, ProgramError> {
+        Ok(self
+            .try_div(multiple)?
+            .try_round_u64()?
+            .checked_mul(multiple)
+            .ok_or(MathError::MulOverflow)?)
+    }
+
+    /// Calculates 1 / self
+    pub fn try_recip(&self) -> Result<Self, ProgramError> {
+        Self::one().try_div(*self)
+    }
+
+    /// Calculates the harmonic mean n / (1/x1 + ... + 1/xn)
+    pub fn try_harmonic_mean(values: &[Self]) -> Result<Self, ProgramError> {
+        if values.is_empty() {
+            return Err(MathError::EmptyInput.into());
+        }
+        let recip_sum = values
+            .iter()
+            .try_fold(Self::zero(), |acc, value| acc.try_add(value.try_recip()?))?;
+        Self::from(values.len() as u64).try_div(recip_sum)
+    }
+
+    /// Calculates self / total as a percentage
+    pub fn try_percent_of(&self, total: Self) -> Result<Self, ProgramError> {
+        self.try_div(total)?.try_mul(Self::from(100u64))
+    }
+
+    /// Multiply without dividing by WAD, the result is scaled by WAD^2
+    pub fn try_mul_raw(&self, rhs: Self) -> Result<U256, ProgramError> {
+        Ok(U256::from(self.0)
+            .checked_mul(U256::from(rhs.0))
+            .ok_or(MathError::MulOverflow)?)
+    }
+
+    /// Decompose into the normalized (mantissa, exponent) pair where
+    /// value = mantissa * 10^exponent
+    pub fn to_mantissa_exp(&self) -> Result<(u64, i8), ProgramError> {
+        if self.0.is_zero() {
+            return Ok((0, 0));
+        }
+        let ten = U192::from(10u64);
+        let mut mantissa = self.0;
+        let mut exponent = -(SCALE as i8);
+        while (mantissa % ten).is_zero() {
+            mantissa = mantissa / ten;
+            exponent += 1;
+        }
+        let mantissa = u64::try_from(mantissa).map_err(|_| MathError::UnableToRoundU64)?;
+        Ok((mantissa, exponent))
+    }
+
+    /// Create decimal from mantissa * 10^exponent, truncating digits
+    /// beyond 18 decimal places
+    pub fn from_mantissa_exp(mantissa: u64, exponent: i8) -> Result<Self, ProgramError> {
+        Self::normalize_oracle_price(mantissa, exponent as i32)
+    }
+
+    /// Create decimal from an oracle price * 10^expo, truncating digits
+    /// beyond 18 decimal places
+    pub fn normalize_oracle_price(price: u64, expo: i32) -> Result<Self, ProgramError> {
+        let scale = expo as i64 + SCALE as i64;
+        let price = U192::from(price);
+        if scale >= 0 {
+            Ok(Self(
+                price
+                    .checked_mul(Self::try_pow10(scale as u32)?)
+                    .ok_or(MathError::MulOverflow)?,
+            ))
         } else {
-            scaled_val.insert(scaled_val.len() - SCALE, '.');
+            Ok(Self(
+                Self::try_pow10(-scale as u32).map_or(U192::zero(), |divisor| price / divisor),
+            ))
         }
-        f.write_str(&scaled_val)
     }
-}
 
-impl From<u64> for Decimal {
-    fn from(val: u64) -> Self {
-        Self(Self::wad() * U192::from(val))
+    /// Create decimal from a Pyth price * 10^exponent, rejecting negative prices
+    pub fn from_pyth_price(price: i64, exponent: i32) -> Result<Self, ProgramError> {
+        let price = u64::try_from(price).map_err(|_| MathError::NegativeValue)?;
+        Self::normalize_oracle_price(price, exponent)
     }
-}
 
-impl From<u128> for Decimal {
-    fn from(val: u128) -> Self {
-        Self(Self::wad() * U192::from(val))
+    /// Convert a token amount to atoms at the given number of decimals,
+    /// flooring any sub-atom remainder
+    pub fn to_atoms(&self, decimals: u8) -> Result<u64, ProgramError> {
+        let atoms = self
+            .0
+            .checked_mul(Self::try_pow10(decimals as u32)?)
+            .ok_or(MathError::MulOverflow)?
+            / Self::wad();
+        Ok(u64::try_from(atoms).map_err(|_| MathError::UnableToRoundU64)?)
     }
-}
 
-impl From<Rate> for Decimal {
-    fn from(val: Rate) -> Self {
-        Self(U192::from(val.to_scaled_val()))
+    /// Like `to_atoms` but also reports whether a sub-atom remainder was
+    /// dropped
+    pub fn to_atoms_exact(&self, decimals: u8) -> Result<(u64, bool), ProgramError> {
+        let scaled = self
+            .0
+            .checked_mul(Self::try_pow10(decimals as u32)?)
+            .ok_or(MathError::MulOverflow)?;
+        let atoms = u64::try_from(scaled / Self::wad()).map_err(|_| MathError::UnableToRoundU64)?;
+        Ok((atoms, !(scaled % Self::wad()).is_zero()))
     }
-}
 
-impl TryAdd for Decimal {
-    fn try_add(self, rhs: Self) -> Result<Self, ProgramError> {
-        Ok(Self(
-            self.0.checked_add(rhs.0).ok_or(MathError::AddOverflow)?,
-        ))
+    /// Like `to_atoms` but clamps to u64::MAX on overflow. This is lossy
+    /// and only intended for estimates, never for transfers.
+    pub fn to_atoms_saturating(&self, decimals: u8) -> u64 {
+        self.to_atoms(decimals).unwrap_or(u64::MAX)
     }
-}
 
-impl TrySub for Decimal {
-    fn try_sub(self, rhs: Self) -> Result<Self, ProgramError> {
-        Ok(Self(
-            self.0.checked_sub(rhs.0).ok_or(MathError::MulOverflow)?,
-        ))
+    /// Largest amount that converts to at most u64::MAX atoms,
+    /// u64::MAX / 10^decimals
+    pub fn max_representable(decimals: u8) -> Self {
+        let max_atoms = Self::from(u64::MAX).0;
+        Self(Self::try_pow10(decimals as u32).map_or(U192::zero(), |divisor| max_atoms / divisor))
     }
-}
 
-impl TryDiv<u64> for Decimal {
-    fn try_div(self, rhs: u64) -> Result<Self, ProgramError> {
-        Ok(Self(
-            self.0
-                .checked_div(U192::from(rhs))
-                .ok_or(MathError::DividedByZero)?,
-        ))
+    /// Update a running average of n - 1 values with the n-th value
+    pub fn try_rolling_average(
+        current_avg: Self,
+        new_value: Self,
+        n: u64,
+    ) -> Result<Self, ProgramError> {
+        let prev_count = n.checked_sub(1).ok_or(MathError::DividedByZero)?;
+        current_avg
+            .try_mul(prev_count)?
+            .try_add(new_value)?
+            .try_div(n)
     }
-}
 
-impl TryDiv<Rate> for Decimal {
-    fn try_div(self, rhs: Rate) -> Result<Self, ProgramError> {
-        self.try_div(Self::from(rhs))
+    /// Calculates the exponential moving average
+    /// alpha * new_value + (1 - alpha) * prev_ema
+    pub fn try_ema(prev_ema: Self, new_value: Self, alpha: Rate) -> Result<Self, ProgramError> {
+        new_value
+            .try_mul(alpha)?
+            .try_add(prev_ema.try_mul(alpha.complement()?)?)
     }
-}
 
-impl TryDiv<Decimal> for Decimal {
-    fn try_div(self, rhs: Self) -> Result<Self, ProgramError> {
+    /// Calculates sum(amounts[i] * prices[i])
+    pub fn weighted_sum(amounts: &[Self], prices: &[Self]) -> Result<Self, ProgramError> {
+        Self::try_sum_product(amounts, prices)
+    }
+
+    /// Calculates the dot product sum(a[i] * b[i])
+    pub fn try_sum_product(a: &[Self], b: &[Self]) -> Result<Self, ProgramError> {
+        if a.len() != b.len() {
+            return Err(MathError::LengthMismatch.into());
+        }
+        a.iter()
+            .zip(b.iter())
+            .try_fold(Self::zero(), |acc, (x, y)| acc.try_add(x.try_mul(*y)?))
+    }
+
+    /// Natural logarithm, errors if the result would be negative
+    pub fn try_ln(&self) -> Result<Self, ProgramError> {
+        match self.try_ln_signed()? {
+            (ln, false) => Ok(ln),
+            _ => Err(MathError::NegativeValue.into()),
+        }
+    }
+
+    /// Calculates e^self
+    pub fn try_exp(&self) -> Result<Self, ProgramError> {
+        self.try_exp_signed(false)
+    }
+
+    /// Calculates self^exp for a fractional exponent
+    pub fn try_pow_decimal(&self, exp: Self) -> Result<Self, ProgramError> {
+        if exp.0.is_zero() {
+            return Ok(Self::one());
+        }
+        if self.0.is_zero() {
+            return Ok(Self::zero());
+        }
+        let (ln, negative) = self.try_ln_signed()?;
+        ln.try_mul(exp)?.try_exp_signed(negative)
+    }
+
+    // self^exp by square and multiply
+    fn try_pow_u64(&self, mut exp: u64) -> Result<Self, ProgramError> {
+        let mut base = *self;
+        let mut ret = Self::one();
+        while exp > 0 {
+            if exp % 2 != 0 {
+                ret = ret.try_mul(base)?;
+            }
+            exp /= 2;
+            if exp > 0 {
+                base = base.try_mul(base)?;
+            }
+        }
+        Ok(ret)
+    }
+
+    /// Map a price to the tick floor(ln(price) / ln(tick_size)), the
+    /// largest tick whose `try_tick_to_price` does not exceed the price
+    pub fn try_price_to_tick(&self, tick_size: Self) -> Result<i64, ProgramError> {
+        let ln_tick_size = tick_size.try_ln()?;
+        let (ln_price, negative) = self.try_ln_signed()?;
+        let ticks = ln_price.try_div(ln_tick_size)?;
+        let mut tick = if negative {
+            let tick =
+                i64::try_from(ticks.try_ceil_u64()?).map_err(|_| MathError::UnableToRoundU64)?;
+            -tick
+        } else {
+            i64::try_from(ticks.try_floor_u64()?).map_err(|_| MathError::UnableToRoundU64)?
+        };
+
+        // The logarithms truncate, so step onto the exact tick boundary
+        let price_at = |tick: i64| Self::try_tick_to_price(tick, tick_size);
+        while price_at(tick)? > *self {
+            tick = tick.checked_sub(1).ok_or(MathError::UnableToRoundU64)?;
+        }
+        while tick < i64::MAX && matches!(price_at(tick + 1), Ok(price) if price <= *self) {
+            tick += 1;
+        }
+        Ok(tick)
+    }
+
+    /// Map a tick back to its price tick_size^tick
+    pub fn try_tick_to_price(tick: i64, tick_size: Self) -> Result<Self, ProgramError> {
+        let price = tick_size.try_pow_u64(tick.unsigned_abs())?;
+        if tick < 0 {
+            price.try_recip()
+        } else {
+            Ok(price)
+        }
+    }
+
+    /// Natural logarithm from a [3/3] Padé approximant. Accurate to about
+    /// 1e-9 while using far fewer multiplications than try_ln.
+    pub fn try_ln_approx_pade(&self) -> Result<Self, ProgramError> {
+        if *self < Self::one() {
+            return Err(MathError::NegativeValue.into());
+        }
+
+        // Reduce to m / 2^(j/4) in [1, 2^(1/4)) where the approximant is accurate
+        let (m, k) = self.split_log2();
+        let quarter_powers_of_two = [
+            Self::from_scaled_val(1_189_207_115_002_721_067),
+            Self::from_scaled_val(1_414_213_562_373_095_049),
+            Self::from_scaled_val(1_681_792_830_507_429_086),
+        ];
+        let j = quarter_powers_of_two
+            .iter()
+            .take_while(|power| m >= **power)
+            .count();
+        let m = match j {
+            0 => m,
+            _ => m.try_div(quarter_powers_of_two[j - 1])?,
+        };
+
+        // ln(1 + x) ~= x * (60 + 60x + 11x^2) / (60 + 90x + 36x^2 + 3x^3)
+        let x = m.try_sub(Self::one())?;
+        let x_squared = x.try_mul(x)?;
+        let x_cubed = x_squared.try_mul(x)?;
+        let numerator = Self::from(60u64)
+            .try_add(x.try_mul(60u64)?)?
+            .try_add(x_squared.try_mul(11u64)?)?
+            .try_mul(x)?;
+        let denominator = Self::from(60u64)
+            .try_add(x.try_mul(90u64)?)?
+            .try_add(x_squared.try_mul(36u64)?)?
+            .try_add(x_cubed.try_mul(3u64)?)?;
+
+        numerator
+            .try_div(denominator)?
+            .try_add(Self::ln2().try_mul(k as u64)?)?
+            .try_add(Self::ln2().try_mul(j as u64)?.try_div(4u64)?)
+    }
+
+    /// Check that lo <= self <= hi
+    pub fn require_in_range(&self, lo: Self, hi: Self) -> Result<(), ProgramError> {
+        if *self < lo || *self > hi {
+            return Err(MathError::OutOfRange.into());
+        }
+        Ok(())
+    }
+
+    /// Calculates the running products [v1, v1 * v2, v1 * v2 * v3, ...]
+    pub fn try_cumulative_product(values: &[Self]) -> Result<Vec<Self>, ProgramError> {
+        let mut product = Self::one();
+        values
+            .iter()
+            .map(|value| {
+                product = product.try_mul(*value)?;
+                Ok(product)
+            })
+            .collect()
+    }
+
+    /// Divide by u64, rounding the last digit up
+    pub fn try_ceil_div(&self, rhs: u64) -> Result<Self, ProgramError> {
+        if rhs == 0 {
+            return Err(MathError::DividedByZero.into());
+        }
+        let rhs = U192::from(rhs);
         Ok(Self(
             self.0
-                .checked_mul(Self::wad())
-                .ok_or(MathError::MulOverflow)?
-                .checked_div(rhs.0)
-                .ok_or(MathError::DividedByZero)?,
+                .checked_add(rhs - U192::one())
+                .ok_or(MathError::AddOverflow)?
+                / rhs,
         ))
     }
-}
 
-impl TryMul<u64> for Decimal {
-    fn try_mul(self, rhs: u64) -> Result<Self, ProgramError> {
+    /// Divide by u64, rounding the last digit down
+    pub fn try_floor_div(&self, rhs: u64) -> Result<Self, ProgramError> {
+        self.try_div(rhs)
+    }
+
+    /// Divide, erroring with DivisorTooSmall when rhs < min_divisor
+    pub fn try_div_guarded(&self, rhs: Self, min_divisor: Self) -> Result<Self, ProgramError> {
+        if rhs < min_divisor {
+            return Err(MathError::DivisorTooSmall.into());
+        }
+        self.try_div(rhs)
+    }
+
+    /// Divide by u64 and floor the quotient to u64 in a single step
+    pub fn try_floor_div_u64(&self, rhs: u64) -> Result<u64, ProgramError> {
+        let quotient = self
+            .0
+            .checked_div(Self::wad() * U192::from(rhs))
+            .ok_or(MathError::DividedByZero)?;
+        Ok(u64::try_from(quotient).map_err(|_| MathError::UnableToRoundU64)?)
+    }
+
+    /// Divide by u64 and ceil the quotient to u64 in a single step
+    pub fn try_ceil_div_u64(&self, rhs: u64) -> Result<u64, ProgramError> {
+        if rhs == 0 {
+            return Err(MathError::DividedByZero.into());
+        }
+        let divisor = Self::wad() * U192::from(rhs);
+        let quotient = self
+            .0
+            .checked_add(divisor - U192::one())
+            .ok_or(MathError::AddOverflow)?
+            / divisor;
+        Ok(u64::try_from(quotient).map_err(|_| MathError::UnableToRoundU64)?)
+    }
+
+    /// Discount with simple interest, self / (1 + rate * days / days_per_year)
+    pub fn try_discount(
+        &self,
+        rate: Rate,
+        days: u64,
+        days_per_year: u64,
+    ) -> Result<Self, ProgramError> {
+        let accrued = Self::from(rate).try_mul(days)?.try_div(days_per_year)?;
+        self.try_div(Self::one().try_add(accrued)?)
+    }
+
+    /// Square root, rounded down to the last digit
+    pub fn try_sqrt(&self) -> Result<Self, ProgramError> {
+        let squared = U256::from(self.0) * U256::from(Self::wad());
+        Ok(Self(squared.integer_sqrt().try_narrow()?))
+    }
+
+    /// Square root, rounded up to the last digit so that the result
+    /// squared is never less than self
+    pub fn try_sqrt_ceil(&self) -> Result<Self, ProgramError> {
+        let squared = U256::from(self.0) * U256::from(Self::wad());
+        let root = squared.integer_sqrt();
+        if root * root < squared {
+            Ok(Self((root + 1).try_narrow()?))
+        } else {
+            Ok(Self(root.try_narrow()?))
+        }
+    }
+
+    /// Interpolate logarithmically, start * (end / start)^t
+    pub fn try_interpolate_log(start: Self, end: Self, t: Rate) -> Result<Self, ProgramError> {
+        start.try_mul(end.try_div(start)?.try_pow_decimal(Self::from(t))?)
+    }
+
+    /// Calculates the power-law fee scale * amount^exponent
+    pub fn try_power_law_fee(
+        amount: Self,
+        exponent: Self,
+        scale: Self,
+    ) -> Result<Self, ProgramError> {
+        scale.try_mul(amount.try_pow_decimal(exponent)?)
+    }
+
+    /// Divide by 10^n
+    pub fn try_div_pow10(&self, n: u8) -> Result<Self, ProgramError> {
         Ok(Self(
-            self.0
-                .checked_mul(U192::from(rhs))
-                .ok_or(MathError::MulOverflow)?,
+            Self::try_pow10(n as u32).map_or(U192::zero(), |divisor| self.0 / divisor),
         ))
     }
-}
 
-impl TryMul<Rate> for Decimal {
-    fn try_mul(self, rhs: Rate) -> Result<Self, ProgramError> {
-        self.try_mul(Self::from(rhs))
+    /// Divide by 10^n, also reporting whether nonzero digits were lost
+    /// below WAD precision
+    pub fn try_div_pow10_lossy(&self, n: u8) -> Result<(Self, bool), ProgramError> {
+        Ok(match Self::try_pow10(n as u32) {
+            Ok(divisor) => (Self(self.0 / divisor), !(self.0 % divisor).is_zero()),
+            Err(_) => (Self::zero(), !self.0.is_zero()),
+        })
     }
-}
 
-impl TryMul<Decimal> for Decimal {
-    fn try_mul(self, rhs: Self) -> Result<Self, ProgramError> {
+    /// Create decimal from a ratio of token atoms, adjusting for each
+    /// token's decimals: (num * 10^den_decimals) / (den * 10^num_decimals)
+    pub fn from_token_price(
+        num: u64,
+        den: u64,
+        num_decimals: u8,
+        den_decimals: u8,
+    ) -> Result<Self, ProgramError> {
+        let numerator = Self::from(num)
+            .0
+            .checked_mul(Self::try_pow10(den_decimals as u32)?)
+            .ok_or(MathError::MulOverflow)?;
+        let denominator = U192::from(den)
+            .checked_mul(Self::try_pow10(num_decimals as u32)?)
+            .ok_or(MathError::MulOverflow)?;
         Ok(Self(
-            self.0
-                .checked_mul(rhs.0)
-                .ok_or(MathError::MulOverflow)?
-                .checked_div(Self::wad())
+            numerator
+                .checked_div(denominator)
                 .ok_or(MathError::DividedByZero)?,
         ))
     }
-}
-impl Sealed for Decimal {}
-impl Pack for Decimal {
-    const LEN: usize = 16;
-    fn pack_into_slice(&self, dst: &mut [u8]) {
-        let output = array_mut_ref![dst, 0, Decimal::LEN];
-        *output = self
-            .to_scaled_val()
-            .expect("Decimal cannot be packed")
-            .to_le_bytes();
+
+    /// Fractional part, self - floor(self)
+    pub fn fract(&self) -> Self {
+        Self(self.0 % Self::wad())
     }
-    fn unpack_from_slice(src: &[u8]) -> Result<Self, ProgramError> {
-        let input = array_ref![src, 0, Decimal::LEN];
-        Ok(Decimal::from_scaled_val(u128::from_le_bytes(*input)))
+
+    /// Integer part, floor(self)
+    pub fn trunc(&self) -> Self {
+        Self(self.0 - self.0 % Self::wad())
     }
-}
 
-#[cfg(test)]
-mod test {
-    use super::*;
-    use crate::SCALE;
+    /// Format as a percentage rounded half up to the given decimal places
+    pub fn to_string_percent_rounded(&self, decimal_places: usize) -> String {
+        // Round at two more places, then shift the decimal point instead
+        // of multiplying by 100, so values near the maximum do not overflow
+        let digits = DecimalFormatter::new(self)
+            .decimal_places(decimal_places + 2)
+            .to_string();
+        let (int_part, frac_part) = digits.split_at(digits.len() - decimal_places - 3);
+        let int_part = format!("{}{}", int_part, &frac_part[1..3]);
+        let int_part = match int_part.trim_start_matches('0') {
+            "" => "0",
+            int_part => int_part,
+        };
+        if decimal_places > 0 {
+            format!("{}.{}%", int_part, &frac_part[3..])
+        } else {
+            format!("{}%", int_part)
+        }
+    }
 
-    #[test]
-    fn test_scaler() {
-        assert_eq!(U192::exp10(SCALE), Decimal::wad());
+    /// Format as a percentage rounded half up to the given decimal places,
+    /// without the "%" sign
+    pub fn try_ratio_to_percentage(&self, decimal_places: u8) -> Result<String, ProgramError> {
+        let percent = self.try_mul(100u64)?;
+        Ok(DecimalFormatter::new(&percent)
+            .decimal_places(decimal_places as usize)
+            .to_string())
+    }
+
+    /// Sum a slice, erroring on the first overflow
+    pub fn try_add_all(values: &[Self]) -> Result<Self, ProgramError> {
+        values
+            .iter()
+            .try_fold(Self::zero(), |acc, value| acc.try_add(*value))
+    }
+
+    /// Compare a / b against c / d without dividing
+    pub fn try_cross_multiply_cmp(
+        a: Self,
+        b: Self,
+        c: Self,
+        d: Self,
+    ) -> Result<Ordering, ProgramError> {
+        if b.0.is_zero() || d.0.is_zero() {
+            return Err(MathError::DividedByZero.into());
+        }
+        Ok(a.try_mul_raw(d)?.cmp(&b.try_mul_raw(c)?))
+    }
+
+    /// Calculates the annualized return (end / start)^(365 / days) - 1,
+    /// erroring with NegativeValue on losses since they cannot be represented
+    pub fn try_annualized_return(start: Self, end: Self, days: u64) -> Result<Self, ProgramError> {
+        let years_inverse = Self::from(365u64).try_div(days)?;
+        let growth = end.try_div(start)?.try_pow_decimal(years_inverse)?;
+        if growth < Self::one() {
+            return Err(MathError::NegativeValue.into());
+        }
+        growth.try_sub(Self::one())
+    }
+
+    /// Check |self - other| <= max(self, other) * rel_tol
+    pub fn approx_eq_rel(&self, other: Self, rel_tol: Rate) -> Result<bool, ProgramError> {
+        let (larger, smaller) = if *self > other {
+            (*self, other)
+        } else {
+            (other, *self)
+        };
+        Ok(larger.try_sub(smaller)? <= larger.try_mul(rel_tol)?)
+    }
+
+    /// Calculates the log return ln(end / start). Losses would give a
+    /// negative result, so they error with NegativeValue.
+    pub fn try_log_return(start: Self, end: Self) -> Result<Self, ProgramError> {
+        end.try_div(start)?.try_ln()
+    }
+
+    /// Kelly bet fraction
+    /// (win_prob * win_frac - loss_prob * loss_frac) / (win_frac * loss_frac),
+    /// erroring with NegativeValue when the bet has a negative edge
+    pub fn try_kelly_fraction(
+        win_prob: Rate,
+        win_frac: Self,
+        loss_frac: Self,
+    ) -> Result<Self, ProgramError> {
+        let expected_win = win_frac.try_mul(win_prob)?;
+        let expected_loss = loss_frac.try_mul(win_prob.complement()?)?;
+        if expected_win < expected_loss {
+            return Err(MathError::NegativeValue.into());
+        }
+        expected_win
+            .try_sub(expected_loss)?
+            .try_div(win_frac.try_mul(loss_frac)?)
+    }
+
+    /// Calculates the weighted harmonic mean sum(w) / sum(w / v)
+    pub fn try_harmonic_weighted_average(
+        values: &[Self],
+        weights: &[Self],
+    ) -> Result<Self, ProgramError> {
+        if values.len() != weights.len() {
+            return Err(MathError::LengthMismatch.into());
+        }
+        if values.is_empty() {
+            return Err(MathError::EmptyInput.into());
+        }
+        let weighted_recip_sum = values
+            .iter()
+            .zip(weights.iter())
+            .try_fold(Self::zero(), |acc, (value, weight)| {
+                acc.try_add(weight.try_div(*value)?)
+            })?;
+        Self::try_add_all(weights)?.try_div(weighted_recip_sum)
+    }
+
+    /// Convert to binary fixed point with frac_bits fractional bits
+    /// (e.g. 64 for Q64.64), rounding down. The decimal fraction generally
+    /// has no exact binary form, so up to 2^-frac_bits is lost.
+    pub fn to_i128_q(&self, frac_bits: u32) -> Result<i128, ProgramError> {
+        if frac_bits > 127 {
+            return Err(MathError::OutOfRange.into());
+        }
+        if self.0.bits() + frac_bits as usize > 256 {
+            return Err(MathError::MulOverflow.into());
+        }
+        let q = (U256::from(self.0) << frac_bits as usize) / U256::from(WAD);
+        let q = u128::try_from(q).map_err(|_| MathError::UnableToRoundU128)?;
+        Ok(i128::try_from(q).map_err(|_| MathError::UnableToRoundU128)?)
+    }
+
+    /// Create decimal from binary fixed point with frac_bits fractional
+    /// bits, rounding down to 18 decimal places
+    pub fn from_i128_q(q: i128, frac_bits: u32) -> Result<Self, ProgramError> {
+        if frac_bits > 127 {
+            return Err(MathError::OutOfRange.into());
+        }
+        let q = u128::try_from(q).map_err(|_| MathError::NegativeValue)?;
+        let scaled_val = (U256::from(q) * U256::from(WAD)) >> frac_bits as usize;
+        Ok(Self(scaled_val.try_narrow()?))
+    }
+
+    /// Divide, rounding the last digit half to even
+    pub fn try_div_half_even(&self, rhs: Self) -> Result<Self, ProgramError> {
+        Ok(Self(
+            self.0
+                .checked_mul(Self::wad())
+                .ok_or(MathError::MulOverflow)?
+                .try_div_rounding(rhs.0, RoundingMode::HalfEven)?,
+        ))
+    }
+
+    /// Calculates the impermanent loss 1 - 2 * sqrt(r) / (1 + r) where
+    /// r = current_ratio / initial_ratio
+    pub fn try_impermanent_loss(
+        initial_ratio: Self,
+        current_ratio: Self,
+    ) -> Result<Self, ProgramError> {
+        let r = current_ratio.try_div(initial_ratio)?;
+        let hold_ratio = r
+            .try_sqrt()?
+            .try_mul(2u64)?
+            .try_div(Self::one().try_add(r)?)?;
+        // by AM-GM hold_ratio <= 1, but clamp against rounding
+        Self::one().try_sub(hold_ratio.min(Self::one()))
+    }
+
+    /// Risk parity weights (1 / var_i) / sum(1 / var_j)
+    pub fn try_risk_parity_weights(variances: &[Self]) -> Result<Vec<Self>, ProgramError> {
+        if variances.is_empty() {
+            return Err(MathError::EmptyInput.into());
+        }
+        let recips = variances
+            .iter()
+            .map(Self::try_recip)
+            .collect::<Result<Vec<_>, _>>()?;
+        let recip_sum = Self::try_add_all(&recips)?;
+        recips
+            .iter()
+            .map(|recip| recip.try_div(recip_sum))
+            .collect()
+    }
+
+    /// Find a root of f with Newton's method, starting from initial.
+    /// f and its derivative df return (magnitude, is_negative) since
+    /// decimals are unsigned. Iteration stops once two successive
+    /// estimates differ by at most one ulp (10^-18).
+    pub fn try_newton<F, DF>(
+        initial: Self,
+        f: F,
+        df: DF,
+        max_iter: u32,
+    ) -> Result<Self, ProgramError>
+    where
+        F: Fn(Self) -> Result<(Self, bool), ProgramError>,
+        DF: Fn(Self) -> Result<(Self, bool), ProgramError>,
+    {
+        let ulp = Self::from_scaled_val(1);
+        let mut x = initial;
+        for _ in 0..max_iter {
+            let (fx, fx_negative) = f(x)?;
+            let (dfx, dfx_negative) = df(x)?;
+            let step = fx.try_div(dfx)?;
+            // x - f(x) / df(x), moving up when the quotient is negative
+            let next = if fx_negative != dfx_negative {
+                x.try_add(step)?
+            } else {
+                x.try_sub(step)?
+            };
+            if next.abs_diff(x) <= ulp {
+                return Ok(next);
+            }
+            x = next;
+        }
+        Err(MathError::DidNotConverge.into())
+    }
+
+    /// Calculates the time-weighted average sum(p_i * d_i) / sum(d_i)
+    pub fn try_time_weighted_average(
+        prices: &[Self],
+        durations: &[u64],
+    ) -> Result<Self, ProgramError> {
+        if prices.len() != durations.len() {
+            return Err(MathError::LengthMismatch.into());
+        }
+        if prices.is_empty() {
+            return Err(MathError::EmptyInput.into());
+        }
+        let (weighted_sum, total_duration) = prices.iter().zip(durations.iter()).try_fold(
+            (Self::zero(), 0u64),
+            |(sum, total), (price, duration)| -> Result<_, ProgramError> {
+                Ok((
+                    sum.try_add(price.try_mul(*duration)?)?,
+                    total.checked_add(*duration).ok_or(MathError::AddOverflow)?,
+                ))
+            },
+        )?;
+        weighted_sum.try_div(total_duration)
+    }
+
+    /// Calculates amount * percent / 100
+    pub fn percent_of(amount: Self, percent: u8) -> Result<Self, ProgramError> {
+        amount.try_mul(percent as u64)?.try_div(100u64)
+    }
+
+    /// Calculates amount * bips / 10000
+    pub fn bips_of(amount: Self, bips: u64) -> Result<Self, ProgramError> {
+        amount.try_mul(bips)?.try_div(10_000u64)
+    }
+
+    /// Arithmetic mean of a slice, erroring with EmptyInput if empty
+    pub fn try_arithmetic_mean(values: &[Self]) -> Result<Self, ProgramError> {
+        if values.is_empty() {
+            return Err(MathError::EmptyInput.into());
+        }
+        Self::try_add_all(values)?.try_div(values.len() as u64)
+    }
+
+    /// Population variance sum((x - mean)^2) / n
+    pub fn try_variance(values: &[Self]) -> Result<Self, ProgramError> {
+        let mean = Self::try_arithmetic_mean(values)?;
+        values
+            .iter()
+            .try_fold(Self::zero(), |acc, value| {
+                let deviation = value.abs_diff(mean);
+                acc.try_add(deviation.try_mul(deviation)?)
+            })?
+            .try_div(values.len() as u64)
+    }
+
+    /// Population standard deviation
+    pub fn try_standard_deviation(values: &[Self]) -> Result<Self, ProgramError> {
+        Self::try_variance(values)?.try_sqrt()
+    }
+
+    /// Population covariance sum((x - mean_x) * (y - mean_y)) / n, erroring
+    /// with NegativeValue when the series move in opposite directions
+    pub fn try_covariance(x: &[Self], y: &[Self]) -> Result<Self, ProgramError> {
+        if x.len() != y.len() {
+            return Err(MathError::LengthMismatch.into());
+        }
+        let mean_x = Self::try_arithmetic_mean(x)?;
+        let mean_y = Self::try_arithmetic_mean(y)?;
+        let (positive, negative) = x.iter().zip(y.iter()).try_fold(
+            (Self::zero(), Self::zero()),
+            |(positive, negative), (xi, yi)| -> Result<_, ProgramError> {
+                let product = xi.abs_diff(mean_x).try_mul(yi.abs_diff(mean_y))?;
+                if (*xi >= mean_x) == (*yi >= mean_y) {
+                    Ok((positive.try_add(product)?, negative))
+                } else {
+                    Ok((positive, negative.try_add(product)?))
+                }
+            },
+        )?;
+        if positive < negative {
+            return Err(MathError::NegativeValue.into());
+        }
+        positive.try_sub(negative)?.try_div(x.len() as u64)
+    }
+
+    /// Pearson correlation cov(x, y) / (stdev(x) * stdev(y)), erroring
+    /// with NegativeValue for negatively correlated series
+    pub fn try_correlation(x: &[Self], y: &[Self]) -> Result<Self, ProgramError> {
+        let covariance = Self::try_covariance(x, y)?;
+        covariance
+            .try_div(Self::try_standard_deviation(x)?.try_mul(Self::try_standard_deviation(y)?)?)
+    }
+
+    /// Realized volatility stdev(log_returns) * sqrt(annualization_factor)
+    pub fn try_volatility(
+        log_returns: &[Self],
+        annualization_factor: u64,
+    ) -> Result<Rate, ProgramError> {
+        let volatility = Self::try_standard_deviation(log_returns)?
+            .try_mul(Self::from(annualization_factor).try_sqrt()?)?;
+        Rate::try_from(volatility)
+    }
+
+    /// Sharpe ratio (avg_return - risk_free) / stdev. Returns below the
+    /// risk-free rate would make it negative, so they error with
+    /// NegativeValue.
+    pub fn try_sharpe_ratio(
+        avg_return: Self,
+        risk_free: Rate,
+        stdev: Self,
+    ) -> Result<Self, ProgramError> {
+        let risk_free = Self::from(risk_free);
+        if avg_return < risk_free {
+            return Err(MathError::NegativeValue.into());
+        }
+        avg_return.try_sub(risk_free)?.try_div(stdev)
+    }
+
+    /// Largest decline from a running peak, (peak - trough) / peak
+    pub fn try_max_drawdown(prices: &[Self]) -> Result<Rate, ProgramError> {
+        if prices.is_empty() {
+            return Err(MathError::EmptyInput.into());
+        }
+        let mut peak = Self::zero();
+        let mut max_drawdown = Self::zero();
+        for price in prices.iter() {
+            if *price > peak {
+                peak = *price;
+            } else if !peak.0.is_zero() {
+                let drawdown = peak.try_sub(*price)?.try_div(peak)?;
+                max_drawdown = max_drawdown.max(drawdown);
+            }
+        }
+        Rate::try_from(max_drawdown)
+    }
+
+    /// Compound amount by (1 + rate)^periods, capping the growth factor
+    /// at max_factor
+    pub fn accrue_capped(
+        amount: Self,
+        rate: Rate,
+        periods: u64,
+        max_factor: Self,
+    ) -> Result<Self, ProgramError> {
+        // Square and multiply, stopping once the factor is known to reach
+        // max_factor. Every term is at least one, so a factor or squared
+        // base that overflows is also past the cap.
+        let capped = || amount.try_mul(max_factor);
+        let mut base = Self::one().try_add(Self::from(rate))?;
+        let mut factor = Self::one();
+        let mut exp = periods;
+        while exp > 0 {
+            if exp % 2 != 0 {
+                factor = match factor.try_mul(base) {
+                    Ok(factor) if factor < max_factor => factor,
+                    _ => return capped(),
+                };
+            }
+            exp /= 2;
+            if exp > 0 {
+                base = match base.try_mul(base) {
+                    Ok(base) if base < max_factor => base,
+                    _ => return capped(),
+                };
+            }
+        }
+        amount.try_mul(factor.min(max_factor))
+    }
+
+    /// Bond convexity sum(t * (t + 1) * cf_t / (1 + r)^(t + 2)) / price
+    /// where cashflows[i] is paid at period t = i + 1
+    pub fn try_convexity(cashflows: &[Self], rate: Rate) -> Result<Self, ProgramError> {
+        if cashflows.is_empty() {
+            return Err(MathError::EmptyInput.into());
+        }
+        let growth = Self::one().try_add(rate.into())?;
+        let (price, weighted) = cashflows.iter().enumerate().try_fold(
+            (Self::zero(), Self::zero()),
+            |(price, weighted), (i, cashflow)| -> Result<_, ProgramError> {
+                let t = i as u64 + 1;
+                let present_value = cashflow.try_div(growth.try_pow_u64(t)?)?;
+                let convexity_term = cashflow
+                    .try_mul(t.checked_mul(t + 1).ok_or(MathError::MulOverflow)?)?
+                    .try_div(growth.try_pow_u64(t + 2)?)?;
+                Ok((
+                    price.try_add(present_value)?,
+                    weighted.try_add(convexity_term)?,
+                ))
+            },
+        )?;
+        weighted.try_div(price)
+    }
+
+    /// Subtract, returning (self - rhs, zero) or (zero, rhs - self) when
+    /// rhs is larger, so the shortfall is reported instead of an error
+    pub fn try_sub_or_deficit(&self, rhs: Self) -> (Self, Self) {
+        if *self >= rhs {
+            (self.abs_diff(rhs), Self::zero())
+        } else {
+            (Self::zero(), self.abs_diff(rhs))
+        }
+    }
+
+    /// Sum of the geometric series first * (1 + ratio + ... + ratio^(n - 1))
+    pub fn geometric_series_sum(first: Self, ratio: Rate, n: u64) -> Result<Self, ProgramError> {
+        let ratio = Self::from(ratio);
+        let ratio_pow = ratio.try_pow_u64(n)?;
+        match ratio.cmp(&Self::one()) {
+            Ordering::Equal => first.try_mul(n),
+            Ordering::Less => first
+                .try_mul(Self::one().try_sub(ratio_pow)?)?
+                .try_div(Self::one().try_sub(ratio)?),
+            Ordering::Greater => first
+                .try_mul(ratio_pow.try_sub(Self::one())?)?
+                .try_div(ratio.try_sub(Self::one())?),
+        }
+    }
+
+    /// Macaulay duration sum(t * PV(cf_t)) / sum(PV(cf_t)) where
+    /// PV(cf_t) = cf_t / (1 + rate)^t
+    pub fn try_duration(
+        cashflows: &[Self],
+        times: &[u64],
+        rate: Rate,
+    ) -> Result<Self, ProgramError> {
+        if cashflows.len() != times.len() {
+            return Err(MathError::LengthMismatch.into());
+        }
+        if cashflows.is_empty() {
+            return Err(MathError::EmptyInput.into());
+        }
+        let growth = Self::one().try_add(rate.into())?;
+        let (price, weighted) = cashflows.iter().zip(times.iter()).try_fold(
+            (Self::zero(), Self::zero()),
+            |(price, weighted), (cashflow, t)| -> Result<_, ProgramError> {
+                let present_value = cashflow.try_div(growth.try_pow_u64(*t)?)?;
+                Ok((
+                    price.try_add(present_value)?,
+                    weighted.try_add(present_value.try_mul(*t)?)?,
+                ))
+            },
+        )?;
+        weighted.try_div(price)
+    }
+
+    /// Round half up to the given decimal places, also reporting whether
+    /// any nonzero digits were discarded
+    pub fn round_to(&self, places: u8) -> Result<(Self, bool), ProgramError> {
+        if places as usize >= SCALE {
+            return Ok((*self, false));
+        }
+        let unit = Self::try_pow10((SCALE - places as usize) as u32)?;
+        let remainder = self.0 % unit;
+        let mut rounded = self.0 - remainder;
+        if remainder >= unit - remainder {
+            rounded = rounded.checked_add(unit).ok_or(MathError::AddOverflow)?;
+        }
+        Ok((Self(rounded), !remainder.is_zero()))
+    }
+
+    /// Rescale two amounts with different token decimals to the larger
+    /// decimal count, returning both amounts and that count
+    pub fn align_scales(
+        a: Self,
+        a_decimals: u8,
+        b: Self,
+        b_decimals: u8,
+    ) -> Result<(Self, Self, u8), ProgramError> {
+        let decimals = a_decimals.max(b_decimals);
+        let rescale = |value: Self, value_decimals: u8| -> Result<Self, ProgramError> {
+            Ok(Self(
+                value
+                    .0
+                    .checked_mul(Self::try_pow10((decimals - value_decimals) as u32)?)
+                    .ok_or(MathError::MulOverflow)?,
+            ))
+        };
+        Ok((rescale(a, a_decimals)?, rescale(b, b_decimals)?, decimals))
+    }
+
+    /// Convert to a rate, saturating at one instead of erroring
+    pub fn try_clamp_to_rate(&self) -> Result<Rate, ProgramError> {
+        Rate::try_from((*self).min(Self::one()))
+    }
+
+    /// Smallest value in a slice, None if empty
+    pub fn min_of(values: &[Self]) -> Option<Self> {
+        values.iter().min().copied()
+    }
+
+    /// Largest value in a slice, None if empty
+    pub fn max_of(values: &[Self]) -> Option<Self> {
+        values.iter().max().copied()
+    }
+
+    /// Truncate to n decimal places without rounding
+    pub fn try_truncate_to_decimals(&self, n: u8) -> Result<Self, ProgramError> {
+        let dropped = (SCALE as u32)
+            .checked_sub(n as u32)
+            .ok_or(MathError::OutOfRange)?;
+        let unit = Self::try_pow10(dropped)?;
+        Ok(Self(self.0 - self.0 % unit))
+    }
+
+    /// Clamp to [lo, hi], also reporting whether the value was adjusted
+    pub fn clamp_reporting(&self, lo: Self, hi: Self) -> (Self, bool) {
+        if *self < lo {
+            (lo, true)
+        } else if *self > hi {
+            (hi, true)
+        } else {
+            (*self, false)
+        }
+    }
+
+    /// Remainder of self / rhs
+    pub fn try_rem(&self, rhs: Self) -> Result<Self, ProgramError> {
+        Ok(Self(
+            self.0.checked_rem(rhs.0).ok_or(MathError::DividedByZero)?,
+        ))
+    }
+
+    /// Calculates self^exp mod modulus by binary exponentiation on the
+    /// integer parts of self and modulus
+    pub fn try_pow_mod(&self, mut exp: u64, modulus: Self) -> Result<Self, ProgramError> {
+        let modulus = U256::from(modulus.0 / Self::wad());
+        if modulus.is_zero() {
+            return Err(MathError::DividedByZero.into());
+        }
+        let mul_mod = |a: U256, b: U256| -> Result<U256, ProgramError> {
+            Ok(a.checked_mul(b).ok_or(MathError::MulOverflow)? % modulus)
+        };
+        let mut base = U256::from(self.0 / Self::wad()) % modulus;
+        let mut ret = U256::one() % modulus;
+        while exp > 0 {
+            if exp % 2 != 0 {
+                ret = mul_mod(ret, base)?;
+            }
+            exp /= 2;
+            if exp > 0 {
+                base = mul_mod(base, base)?;
+            }
+        }
+        // ret < modulus, so it fits once scaled back by WAD
+        Ok(Self(ret.try_narrow()? * Self::wad()))
+    }
+
+    /// Divide, also reporting whether the quotient is exact at WAD
+    /// precision
+    pub fn try_div_terminating(&self, rhs: Self) -> Result<(Self, bool), ProgramError> {
+        let scaled = self
+            .0
+            .checked_mul(Self::wad())
+            .ok_or(MathError::MulOverflow)?;
+        let quotient = scaled.checked_div(rhs.0).ok_or(MathError::DividedByZero)?;
+        Ok((Self(quotient), (scaled % rhs.0).is_zero()))
+    }
+
+    /// Whether self has no fractional part
+    pub fn is_integer(&self) -> bool {
+        self.fract() == Self::zero()
+    }
+
+    /// Greatest common divisor of two integer decimals
+    pub fn try_gcd(&self, other: Self) -> Result<Self, ProgramError> {
+        if !self.is_integer() || !other.is_integer() {
+            return Err(MathError::OutOfRange.into());
+        }
+        let (mut a, mut b) = (*self, other);
+        while b != Self::zero() {
+            let rem = a.try_rem(b)?;
+            a = b;
+            b = rem;
+        }
+        Ok(a)
+    }
+
+    /// Least common multiple of two integer decimals
+    pub fn try_lcm(&self, other: Self) -> Result<Self, ProgramError> {
+        let gcd = self.try_gcd(other)?;
+        if gcd == Self::zero() {
+            return Ok(Self::zero());
+        }
+        self.try_div(gcd)?.try_mul(other)
+    }
+
+    /// Logarithm in an arbitrary base, ln(self) / ln(base). Negative
+    /// results error with NegativeValue.
+    pub fn try_log_base(&self, base: Self) -> Result<Self, ProgramError> {
+        if base == Self::one() {
+            return Err(MathError::OutOfRange.into());
+        }
+        let (ln_base, base_negative) = base.try_ln_signed()?;
+        let (ln_self, self_negative) = self.try_ln_signed()?;
+        if self_negative != base_negative && !ln_self.0.is_zero() {
+            return Err(MathError::NegativeValue.into());
+        }
+        ln_self.try_div(ln_base)
+    }
+
+    /// Best rational approximation p / q with q <= max_denominator, found
+    /// from the continued fraction expansion of self
+    pub fn try_continued_fraction_approx(
+        &self,
+        max_denominator: u64,
+    ) -> Result<(u64, u64), ProgramError> {
+        if max_denominator == 0 {
+            return Err(MathError::DividedByZero.into());
+        }
+        let to_u64 = |value: u128| -> Result<u64, ProgramError> {
+            Ok(u64::try_from(value).map_err(|_| MathError::UnableToRoundU64)?)
+        };
+        let numerator = u128::try_from(self.0).map_err(|_| MathError::UnableToRoundU128)?;
+        let (mut a, mut b) = (numerator, WAD as u128);
+        while b != 0 {
+            let rem = a % b;
+            a = b;
+            b = rem;
+        }
+        let (numerator, denominator) = (numerator / a, WAD as u128 / a);
+        let max_denominator = max_denominator as u128;
+        if denominator <= max_denominator {
+            return Ok((to_u64(numerator)?, to_u64(denominator)?));
+        }
+
+        // Convergents p0 / q0 and p1 / q1 of the remaining expansion n / d
+        let (mut p0, mut q0, mut p1, mut q1) = (0u128, 1u128, 1u128, 0u128);
+        let (mut n, mut d) = (numerator, denominator);
+        loop {
+            let a = n / d;
+            let q2 = q0 + a * q1;
+            if q2 > max_denominator {
+                break;
+            }
+            let p2 = p0 + a * p1;
+            p0 = p1;
+            q0 = q1;
+            p1 = p2;
+            q1 = q2;
+            let rem = n - a * d;
+            n = d;
+            d = rem;
+        }
+
+        // Pick the closer of the last convergent and the best semiconvergent
+        let k = (max_denominator - q0) / q1;
+        let (p_semi, q_semi) = (p0 + k * p1, q0 + k * q1);
+        // |p / q - self| scaled by q * denominator
+        let scaled_distance = |p: u128, q: u128| {
+            let lhs = U256::from(p) * U256::from(denominator);
+            let rhs = U256::from(numerator) * U256::from(q);
+            if lhs > rhs {
+                lhs - rhs
+            } else {
+                rhs - lhs
+            }
+        };
+        if scaled_distance(p_semi, q_semi) * U256::from(q1)
+            < scaled_distance(p1, q1) * U256::from(q_semi)
+        {
+            Ok((to_u64(p_semi)?, to_u64(q_semi)?))
+        } else {
+            Ok((to_u64(p1)?, to_u64(q1)?))
+        }
+    }
+
+    /// Calculates a * b / c with a 256-bit intermediate and the given
+    /// rounding, e.g. shares = deposit * total_shares / total_assets
+    pub fn mul_div_u128(
+        a: u128,
+        b: u128,
+        c: u128,
+        mode: RoundingMode,
+    ) -> Result<u128, ProgramError> {
+        let quotient = (U256::from(a) * U256::from(b)).try_div_rounding(U256::from(c), mode)?;
+        if quotient > U256::from(u128::MAX) {
+            return Err(MathError::UnableToRoundU128.into());
+        }
+        Ok(quotient.as_u128())
+    }
+
+    /// Pack a rate-sized value into its scaled u64, for compact storage.
+    /// Only values in [0, 18.446744073709551615] are representable.
+    pub fn to_rate_compact(&self) -> Result<u64, ProgramError> {
+        Ok(u64::try_from(self.0).map_err(|_| MathError::UnableToRoundU64)?)
+    }
+
+    /// Unpack a value stored with `to_rate_compact`
+    pub fn from_rate_compact(compact: u64) -> Self {
+        Self(U192::from(compact))
+    }
+
+    /// Continuously compounded amount self * e^(rate * time), with time in
+    /// the same period as rate
+    pub fn try_compound_interest_continuous(
+        &self,
+        rate: Rate,
+        time: Self,
+    ) -> Result<Self, ProgramError> {
+        self.try_mul(Self::from(rate).try_mul(time)?.try_exp()?)
+    }
+
+    /// Calculates (self / rhs) * 10^decimals as an integer with the given
+    /// rounding, e.g. a price quote at 8 decimals
+    pub fn div_to_scaled_u64(
+        &self,
+        rhs: Self,
+        decimals: u8,
+        mode: RoundingMode,
+    ) -> Result<u64, ProgramError> {
+        let scaled = U256::from(self.0)
+            .checked_mul(U256::from(Self::try_pow10(decimals as u32)?))
+            .ok_or(MathError::MulOverflow)?;
+        let quotient = scaled.try_div_rounding(U256::from(rhs.0), mode)?;
+        if quotient > U256::from(u64::MAX) {
+            return Err(MathError::UnableToRoundU64.into());
+        }
+        Ok(quotient.as_u64())
+    }
+
+    /// Add each value into acc, returning the error and the index of the
+    /// failing value. On error acc holds the sum of the values before it.
+    pub fn try_fold_add(acc: &mut Self, values: &[Self]) -> Result<(), (ProgramError, usize)> {
+        for (index, value) in values.iter().enumerate() {
+            *acc = acc.try_add(*value).map_err(|e| (e, index))?;
+        }
+        Ok(())
+    }
+
+    /// Logistic sigmoid 1 / (1 + e^(-k * self)), in [0.5, 1) for unsigned
+    /// inputs
+    pub fn try_sigmoid(&self, k: Self) -> Result<Self, ProgramError> {
+        let decay = self.try_mul(k)?.try_exp_signed(true)?;
+        Self::one().try_add(decay)?.try_recip()
+    }
+
+    /// Create decimal from a u128 ratio, truncating to WAD precision
+    pub fn from_rational_u128(num: u128, den: u128) -> Result<Self, ProgramError> {
+        Self::from_scaled_val(num).try_div(Self::from_scaled_val(den))
+    }
+
+    /// Calculates self * rhs * 10^expo with a single truncation
+    pub fn try_mul_pow10(&self, rhs: Self, expo: i32) -> Result<Self, ProgramError> {
+        let product = self.try_mul_raw(rhs)?;
+        let scale = Self::try_pow10(expo.unsigned_abs());
+        let scaled = if expo >= 0 {
+            product
+                .checked_mul(U256::from(scale?))
+                .ok_or(MathError::MulOverflow)?
+                / U256::from(Self::wad())
+        } else {
+            scale.map_or(U256::zero(), |divisor| {
+                product / (U256::from(divisor) * U256::from(Self::wad()))
+            })
+        };
+        Ok(Self(scaled.try_narrow()?))
+    }
+
+    /// Softmax e^v_i / sum(e^v_j), shifted by the maximum so large values
+    /// do not overflow
+    pub fn try_softmax(values: &[Self]) -> Result<Vec<Self>, ProgramError> {
+        let max = Self::max_of(values).ok_or(MathError::EmptyInput)?;
+        let exps = values
+            .iter()
+            .map(|value| max.try_sub(*value)?.try_exp_signed(true))
+            .collect::<Result<Vec<_>, ProgramError>>()?;
+        let sum = Self::try_add_all(&exps)?;
+        exps.into_iter().map(|exp| exp.try_div(sum)).collect()
+    }
+
+    /// One geometric Brownian motion step
+    /// self * e^((drift - volatility^2 / 2) * dt + volatility * dw * sqrt(dt)).
+    /// Decimal is unsigned, so the shock dw can only be non-negative.
+    pub fn try_gbm_step(
+        &self,
+        drift: Rate,
+        volatility: Rate,
+        dt: Self,
+        dw: Self,
+    ) -> Result<Self, ProgramError> {
+        let volatility = Self::from(volatility);
+        let growth = Self::from(drift)
+            .try_mul(dt)?
+            .try_add(volatility.try_mul(dw)?.try_mul(dt.try_sqrt()?)?)?;
+        let decay = volatility.try_mul(volatility)?.try_div(2u64)?.try_mul(dt)?;
+        let factor = if growth >= decay {
+            growth.try_sub(decay)?.try_exp_signed(false)?
+        } else {
+            decay.try_sub(growth)?.try_exp_signed(true)?
+        };
+        self.try_mul(factor)
+    }
+}
+
+impl fmt::Display for Decimal {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut scaled_val = self.0.to_string();
+        if scaled_val.len() <= SCALE {
+            scaled_val.insert_str(0, &vec!["0"; SCALE - scaled_val.len()].join(""));
+            scaled_val.insert_str(0, "0.");
+        } else {
+            scaled_val.insert(scaled_val.len() - SCALE, '.');
+        }
+        f.write_str(&scaled_val)
+    }
+}
+
+/// Builder for customized decimal display
+pub struct DecimalFormatter<'a> {
+    decimal: &'a Decimal,
+    decimal_places: usize,
+    strip_trailing_zeros: bool,
+    prefix: Option<&'a str>,
+    suffix: Option<&'a str>,
+}
+
+impl<'a> DecimalFormatter<'a> {
+    /// Format with all 18 decimal places and no prefix or suffix
+    pub fn new(decimal: &'a Decimal) -> Self {
+        Self {
+            decimal,
+            decimal_places: SCALE,
+            strip_trailing_zeros: false,
+            prefix: None,
+            suffix: None,
+        }
+    }
+
+    /// Round half up to a fixed number of decimal places
+    pub fn decimal_places(mut self, decimal_places: usize) -> Self {
+        self.decimal_places = decimal_places;
+        self
+    }
+
+    /// Remove trailing zeros after the decimal point
+    pub fn strip_trailing_zeros(mut self, strip_trailing_zeros: bool) -> Self {
+        self.strip_trailing_zeros = strip_trailing_zeros;
+        self
+    }
+
+    /// Text written before the number, e.g. a currency symbol
+    pub fn prefix(mut self, prefix: &'a str) -> Self {
+        self.prefix = Some(prefix);
+        self
+    }
+
+    /// Text written after the number, e.g. a unit
+    pub fn suffix(mut self, suffix: &'a str) -> Self {
+        self.suffix = Some(suffix);
+        self
+    }
+}
+
+impl fmt::Display for DecimalFormatter<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let places = self.decimal_places.min(SCALE);
+        let divisor = U192::exp10(SCALE - places);
+        let scaled_val = self.decimal.0.saturating_add(divisor / 2) / divisor;
+
+        let mut digits = scaled_val.to_string();
+        if digits.len() <= places {
+            digits.insert_str(0, &"0".repeat(places + 1 - digits.len()));
+        }
+        if places > 0 {
+            digits.insert(digits.len() - places, '.');
+        }
+        digits.push_str(&"0".repeat(self.decimal_places - places));
+        if self.strip_trailing_zeros && digits.contains('.') {
+            digits = digits
+                .trim_end_matches('0')
+                .trim_end_matches('.')
+                .to_string();
+        }
+
+        f.write_str(self.prefix.unwrap_or(""))?;
+        f.write_str(&digits)?;
+        f.write_str(self.suffix.unwrap_or(""))
+    }
+}
+
+impl FromStr for Decimal {
+    type Err = ProgramError;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (int_part, frac_part) = match s.find('.') {
+            Some(index) => (&s[..index], &s[index + 1..]),
+            None => (s, ""),
+        };
+        let is_digits = |part: &str| part.chars().all(|c| c.is_ascii_digit());
+        if (int_part.is_empty() && frac_part.is_empty())
+            || frac_part.len() > SCALE
+            || !is_digits(int_part)
+            || !is_digits(frac_part)
+        {
+            return Err(MathError::UnableToParse.into());
+        }
+        let scaled_val = format!("{}{:0<width$}", int_part, frac_part, width = SCALE);
+        Ok(Self(
+            U192::from_dec_str(&scaled_val).map_err(|_| MathError::UnableToParse)?,
+        ))
+    }
+}
+
+impl From<u64> for Decimal {
+    fn from(val: u64) -> Self {
+        Self(Self::wad() * U192::from(val))
+    }
+}
+
+impl From<u128> for Decimal {
+    fn from(val: u128) -> Self {
+        Self(Self::wad() * U192::from(val))
+    }
+}
+
+impl From<Rate> for Decimal {
+    fn from(val: Rate) -> Self {
+        Self(U192::from(val.to_scaled_val()))
+    }
+}
+
+impl TryFrom<Decimal> for u128 {
+    type Error = ProgramError;
+    fn try_from(decimal: Decimal) -> Result<Self, Self::Error> {
+        decimal.to_scaled_val()
+    }
+}
+
+impl TryAdd for Decimal {
+    fn try_add(self, rhs: Self) -> Result<Self, ProgramError> {
+        Ok(Self(
+            self.0.checked_add(rhs.0).ok_or(MathError::AddOverflow)?,
+        ))
+    }
+}
+
+impl TrySub for Decimal {
+    fn try_sub(self, rhs: Self) -> Result<Self, ProgramError> {
+        Ok(Self(
+            self.0.checked_sub(rhs.0).ok_or(MathError::SubUnderflow)?,
+        ))
+    }
+}
+
+impl TryDiv<u64> for Decimal {
+    fn try_div(self, rhs: u64) -> Result<Self, ProgramError> {
+        Ok(Self(
+            self.0
+                .checked_div(U192::from(rhs))
+                .ok_or(MathError::DividedByZero)?,
+        ))
+    }
+}
+
+impl TryDiv<Rate> for Decimal {
+    fn try_div(self, rhs: Rate) -> Result<Self, ProgramError> {
+        self.try_div(Self::from(rhs))
+    }
+}
+
+impl TryDiv<Decimal> for Decimal {
+    fn try_div(self, rhs: Self) -> Result<Self, ProgramError> {
+        Ok(Self(
+            self.0
+                .checked_mul(Self::wad())
+                .ok_or(MathError::MulOverflow)?
+                .checked_div(rhs.0)
+                .ok_or(MathError::DividedByZero)?,
+        ))
+    }
+}
+
+impl TryMul<u64> for Decimal {
+    fn try_mul(self, rhs: u64) -> Result<Self, ProgramError> {
+        Ok(Self(
+            self.0
+                .checked_mul(U192::from(rhs))
+                .ok_or(MathError::MulOverflow)?,
+        ))
+    }
+}
+
+impl TryMul<Rate> for Decimal {
+    fn try_mul(self, rhs: Rate) -> Result<Self, ProgramError> {
+        self.try_mul(Self::from(rhs))
+    }
+}
+
+impl TryMul<Decimal> for Decimal {
+    fn try_mul(self, rhs: Self) -> Result<Self, ProgramError> {
+        Ok(Self(
+            self.0
+                .checked_mul(rhs.0)
+                .ok_or(MathError::MulOverflow)?
+                .checked_div(Self::wad())
+                .ok_or(MathError::DividedByZero)?,
+        ))
+    }
+}
+impl Sealed for Decimal {}
+impl Pack for Decimal {
+    const LEN: usize = 16;
+    fn pack_into_slice(&self, dst: &mut [u8]) {
+        let output = array_mut_ref![dst, 0, Decimal::LEN];
+        *output = self
+            .to_scaled_val()
+            .expect("Decimal cannot be packed")
+            .to_le_bytes();
+    }
+    fn unpack_from_slice(src: &[u8]) -> Result<Self, ProgramError> {
+        let input = array_ref![src, 0, Decimal::LEN];
+        Ok(Decimal::from_scaled_val(u128::from_le_bytes(*input)))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::SCALE;
+
+    fn assert_close(left: Decimal, right: Decimal, epsilon: u128) {
+        let diff = if left > right {
+            left.try_sub(right).unwrap()
+        } else {
+            right.try_sub(left).unwrap()
+        };
+        assert!(
+            diff <= Decimal::from_scaled_val(epsilon),
+            "{} is not within {} of {}",
+            left,
+            Decimal::from_scaled_val(epsilon),
+            right
+        );
+    }
+
+    #[test]
+    fn test_scaler() {
+        assert_eq!(U192::exp10(SCALE), Decimal::wad());
+    }
+
+    #[test]
+    fn test_try_from_decimal_for_u128() {
+        let max = Decimal::from_scaled_val(u128::MAX);
+        assert_eq!(u128::try_from(max).unwrap(), u128::MAX);
+        let above_max = Decimal(max.0 + U192::from(1u64));
+        assert_eq!(
+            u128::try_from(above_max),
+            Err(MathError::UnableToRoundU128.into())
+        );
+    }
+
+    #[test]
+    fn test_harmonic_mean() {
+        let values = [Decimal::from(1u64), Decimal::from(4u64)];
+        assert_eq!(
+            Decimal::try_harmonic_mean(&values).unwrap(),
+            Decimal::from_scaled_val(1_600_000_000_000_000_000)
+        );
+        assert_eq!(
+            Decimal::try_harmonic_mean(&[]),
+            Err(MathError::EmptyInput.into())
+        );
+        assert_eq!(
+            Decimal::try_harmonic_mean(&[Decimal::one(), Decimal::zero()]),
+            Err(MathError::DividedByZero.into())
+        );
+    }
+
+    #[test]
+    fn test_percent_of() {
+        assert_eq!(
+            Decimal::from(25u64)
+                .try_percent_of(Decimal::from(100u64))
+                .unwrap(),
+            Decimal::from(25u64)
+        );
+        assert_eq!(
+            Decimal::one().try_percent_of(Decimal::zero()),
+            Err(MathError::DividedByZero.into())
+        );
+    }
+
+    #[test]
+    fn test_mul_raw() {
+        let a = Decimal::from_scaled_val(1_234_567_890_123_456_789);
+        let b = Decimal::from_scaled_val(9_876_543_210_987_654_321);
+        let raw = a.try_mul_raw(b).unwrap();
+        assert_eq!(U256::from(a.try_mul(b).unwrap().0), raw / U256::from(WAD));
+        let max = Decimal(U192::MAX);
+        assert_eq!(max.try_mul_raw(max), Err(MathError::MulOverflow.into()));
+    }
+
+    #[test]
+    fn test_mantissa_exp() {
+        let cases = [
+            (Decimal::zero(), (0, 0)),
+            (Decimal::one(), (1, 0)),
+            (Decimal::from_scaled_val(1), (1, -18)),
+            (Decimal::from_percent(15), (15, -2)),
+            (
+                Decimal::from_scaled_val(123_456_789_000_000_000_000),
+                (123_456_789, -6),
+            ),
+            (
+                Decimal::from(10_000_000_000_000_000_000_000_000_000u128),
+                (1, 28),
+            ),
+        ];
+        for (decimal, pair) in cases.iter() {
+            assert_eq!(decimal.to_mantissa_exp().unwrap(), *pair);
+            assert_eq!(
+                Decimal::from_mantissa_exp(pair.0, pair.1).unwrap(),
+                *decimal
+            );
+        }
+
+        let too_precise = Decimal::from_scaled_val(u64::MAX as u128 * 10 + 1);
+        assert_eq!(
+            too_precise.to_mantissa_exp(),
+            Err(MathError::UnableToRoundU64.into())
+        );
+        assert_eq!(Decimal::from_mantissa_exp(1, -19).unwrap(), Decimal::zero());
+        assert_eq!(
+            Decimal::from_mantissa_exp(1, 100),
+            Err(MathError::MulOverflow.into())
+        );
+    }
+
+    #[test]
+    fn test_to_atoms_saturating() {
+        let amount = Decimal::from_scaled_val(1_500_000_000_000_000_000);
+        assert_eq!(amount.to_atoms(6).unwrap(), 1_500_000);
+
+        let max = Decimal::from(u64::MAX);
+        assert_eq!(max.to_atoms(0).unwrap(), u64::MAX);
+        assert_eq!(max.to_atoms_saturating(0), u64::MAX);
+        assert_eq!(max.to_atoms(1), Err(MathError::UnableToRoundU64.into()));
+        assert_eq!(max.to_atoms_saturating(1), u64::MAX);
+        assert_eq!(Decimal(U192::MAX).to_atoms_saturating(9), u64::MAX);
+    }
+
+    #[test]
+    fn test_rolling_average() {
+        let mut avg = Decimal::zero();
+        for n in 1..=10 {
+            avg = Decimal::try_rolling_average(avg, Decimal::from(10u64), n).unwrap();
+        }
+        assert_eq!(avg, Decimal::from(10u64));
+
+        let values = [1u64, 3, 8, 4, 9];
+        let mut avg = Decimal::zero();
+        for (i, value) in values.iter().enumerate() {
+            avg = Decimal::try_rolling_average(avg, Decimal::from(*value), i as u64 + 1).unwrap();
+        }
+        let mean = Decimal::from(values.iter().sum::<u64>())
+            .try_div(values.len() as u64)
+            .unwrap();
+        assert_eq!(avg, mean);
+
+        assert_eq!(
+            Decimal::try_rolling_average(avg, avg, 0),
+            Err(MathError::DividedByZero.into())
+        );
+    }
+
+    #[test]
+    fn test_ema() {
+        let prev = Decimal::from(10u64);
+        let new = Decimal::from(20u64);
+        assert_eq!(Decimal::try_ema(prev, new, Rate::one()).unwrap(), new);
+        assert_eq!(Decimal::try_ema(prev, new, Rate::zero()).unwrap(), prev);
+        assert_eq!(
+            Decimal::try_ema(prev, new, Rate::from_percent(50)).unwrap(),
+            Decimal::from(15u64)
+        );
+        assert_eq!(
+            Decimal::try_ema(prev, new, Rate::from_percent(101)),
+            Err(MathError::SubUnderflow.into())
+        );
+    }
+
+    #[test]
+    fn test_weighted_sum() {
+        let amounts = [Decimal::from(2u64), Decimal::from_percent(50)];
+        let prices = [Decimal::from(100u64), Decimal::from(30u64)];
+        assert_eq!(
+            Decimal::weighted_sum(&amounts, &prices).unwrap(),
+            Decimal::from(215u64)
+        );
+        assert_eq!(
+            Decimal::weighted_sum(&amounts, &prices[..1]),
+            Err(MathError::LengthMismatch.into())
+        );
+    }
+
+    #[test]
+    fn test_ln_exp() {
+        let e = Decimal::from_scaled_val(2_718_281_828_459_045_235);
+        assert_eq!(Decimal::one().try_ln().unwrap(), Decimal::zero());
+        assert_close(e.try_ln().unwrap(), Decimal::one(), 100);
+        assert_close(
+            Decimal::from(1_000_000u64).try_ln().unwrap(),
+            Decimal::from_scaled_val(13_815_510_557_964_274_104),
+            100,
+        );
+        assert_eq!(
+            Decimal::from_percent(50).try_ln(),
+            Err(MathError::NegativeValue.into())
+        );
+        assert_eq!(
+            Decimal::zero().try_ln(),
+            Err(MathError::NegativeValue.into())
+        );
+
+        assert_eq!(Decimal::zero().try_exp().unwrap(), Decimal::one());
+        assert_close(Decimal::one().try_exp().unwrap(), e, 100);
+        assert_close(
+            Decimal::from(10u64).try_exp().unwrap(),
+            Decimal::from_scaled_val(22_026_465_794_806_716_516_958),
+            100_000,
+        );
+        assert_eq!(
+            Decimal::from(200u64).try_exp(),
+            Err(MathError::MulOverflow.into())
+        );
+    }
+
+    #[test]
+    fn test_pow_decimal() {
+        let two = Decimal::from(2u64);
+        assert_close(
+            Decimal::from(4u64)
+                .try_pow_decimal(Decimal::from_percent(50))
+                .unwrap(),
+            two,
+            10,
+        );
+        assert_close(
+            two.try_pow_decimal(Decimal::from(10u64)).unwrap(),
+            Decimal::from(1024u64),
+            1_000_000,
+        );
+        assert_close(
+            Decimal::from_percent(25)
+                .try_pow_decimal(Decimal::from_percent(50))
+                .unwrap(),
+            Decimal::from_percent(50),
+            10,
+        );
+        assert_eq!(
+            Decimal::zero().try_pow_decimal(Decimal::zero()).unwrap(),
+            Decimal::one()
+        );
+        assert_eq!(
+            Decimal::zero().try_pow_decimal(two).unwrap(),
+            Decimal::zero()
+        );
+    }
+
+    #[test]
+    fn test_price_to_tick() {
+        let tick_size = Decimal::from_scaled_val(1_000_100_000_000_000_000);
+        assert_eq!(Decimal::one().try_price_to_tick(tick_size).unwrap(), 0);
+        assert_eq!(tick_size.try_price_to_tick(tick_size).unwrap(), 1);
+        assert_eq!(
+            Decimal::from_scaled_val(999_900_000_000_000_000)
+                .try_price_to_tick(tick_size)
+                .unwrap(),
+            -2
+        );
+        assert_eq!(
+            Decimal::one().try_price_to_tick(Decimal::one()),
+            Err(MathError::DividedByZero.into())
+        );
+
+        assert_eq!(
+            Decimal::try_tick_to_price(0, tick_size).unwrap(),
+            Decimal::one()
+        );
+        assert_close(
+            Decimal::try_tick_to_price(1, tick_size).unwrap(),
+            tick_size,
+            10,
+        );
+        assert_close(
+            Decimal::try_tick_to_price(-1, tick_size).unwrap(),
+            tick_size.try_recip().unwrap(),
+            10,
+        );
+        assert_eq!(
+            Decimal::try_tick_to_price(2, tick_size).unwrap(),
+            Decimal::from_scaled_val(1_000_200_010_000_000_000)
+        );
+
+        let ticks = (-50..=50).chain([-100_000, -1_000, 100, 1_000, 10_000, 100_000]);
+        for tick in ticks {
+            let price = Decimal::try_tick_to_price(tick, tick_size).unwrap();
+            assert_eq!(price.try_price_to_tick(tick_size).unwrap(), tick);
+            // Just below a tick's price falls into the tick before it
+            let below = price.try_sub(Decimal::from_scaled_val(1)).unwrap();
+            assert_eq!(below.try_price_to_tick(tick_size).unwrap(), tick - 1);
+        }
+    }
+
+    #[test]
+    fn test_sum_product() {
+        let a = [1u64, 2, 3].map(Decimal::from);
+        let b = [4u64, 5, 6].map(Decimal::from);
+        assert_eq!(
+            Decimal::try_sum_product(&a, &b).unwrap(),
+            Decimal::from(32u64)
+        );
+        assert_eq!(
+            Decimal::try_sum_product(&a, &b[1..]),
+            Err(MathError::LengthMismatch.into())
+        );
+    }
+
+    #[test]
+    fn test_require_in_range() {
+        let lo = Decimal::from(1u64);
+        let hi = Decimal::from(3u64);
+        assert_eq!(
+            Decimal::zero().require_in_range(lo, hi),
+            Err(MathError::OutOfRange.into())
+        );
+        assert_eq!(lo.require_in_range(lo, hi), Ok(()));
+        assert_eq!(Decimal::from(2u64).require_in_range(lo, hi), Ok(()));
+        assert_eq!(hi.require_in_range(lo, hi), Ok(()));
+        assert_eq!(
+            Decimal::from(4u64).require_in_range(lo, hi),
+            Err(MathError::OutOfRange.into())
+        );
+    }
+
+    #[test]
+    fn test_cumulative_product() {
+        let values = [2u64, 3, 4].map(Decimal::from);
+        assert_eq!(
+            Decimal::try_cumulative_product(&values).unwrap(),
+            [2u64, 6, 24].map(Decimal::from).to_vec()
+        );
+        assert!(Decimal::try_cumulative_product(&[]).unwrap().is_empty());
+        assert_eq!(
+            Decimal::try_cumulative_product(&[Decimal(U192::MAX), Decimal::from(2u64)]),
+            Err(MathError::MulOverflow.into())
+        );
+    }
+
+    #[test]
+    fn test_ceil_floor_div() {
+        let one = Decimal::one();
+        assert_eq!(
+            one.try_floor_div(3).unwrap(),
+            Decimal::from_scaled_val(333_333_333_333_333_333)
+        );
+        assert_eq!(
+            one.try_ceil_div(3).unwrap(),
+            Decimal::from_scaled_val(333_333_333_333_333_334)
+        );
+        assert_eq!(one.try_ceil_div(4).unwrap(), one.try_floor_div(4).unwrap());
+        assert_eq!(one.try_ceil_div(0), Err(MathError::DividedByZero.into()));
+        assert_eq!(one.try_floor_div(0), Err(MathError::DividedByZero.into()));
+    }
+
+    #[test]
+    fn test_decimal_formatter() {
+        let d = Decimal::from_scaled_val(1_500_000_000_000_000_000);
+        assert_eq!(
+            DecimalFormatter::new(&d)
+                .decimal_places(2)
+                .prefix("$")
+                .to_string(),
+            "$1.50"
+        );
+        assert_eq!(DecimalFormatter::new(&d).to_string(), d.to_string());
+        assert_eq!(
+            DecimalFormatter::new(&d)
+                .strip_trailing_zeros(true)
+                .suffix(" SOL")
+                .to_string(),
+            "1.5 SOL"
+        );
+        assert_eq!(DecimalFormatter::new(&d).decimal_places(0).to_string(), "2");
+        assert_eq!(
+            DecimalFormatter::new(&Decimal::from_scaled_val(1_005_000_000_000_000_000))
+                .decimal_places(2)
+                .to_string(),
+            "1.01"
+        );
+        assert_eq!(
+            DecimalFormatter::new(&Decimal::from_scaled_val(4_000_000_000_000_000))
+                .decimal_places(3)
+                .to_string(),
+            "0.004"
+        );
+        assert_eq!(
+            DecimalFormatter::new(&Decimal::from(10u64))
+                .strip_trailing_zeros(true)
+                .to_string(),
+            "10"
+        );
+        assert_eq!(
+            DecimalFormatter::new(&Decimal::one())
+                .decimal_places(20)
+                .to_string(),
+            "1.00000000000000000000"
+        );
+    }
+
+    #[test]
+    fn test_discount() {
+        let rate = Rate::from_percent(5);
+        assert_eq!(
+            Decimal::from(100u64).try_discount(rate, 180, 360).unwrap(),
+            Decimal::from_scaled_val(97_560_975_609_756_097_560)
+        );
+        assert_eq!(
+            Decimal::from(100u64).try_discount(rate, 0, 360).unwrap(),
+            Decimal::from(100u64)
+        );
+        assert_eq!(
+            Decimal::from(100u64).try_discount(rate, 180, 0),
+            Err(MathError::DividedByZero.into())
+        );
+    }
+
+    #[test]
+    fn test_sqrt() {
+        let four = Decimal::from(4u64);
+        assert_eq!(four.try_sqrt().unwrap(), Decimal::from(2u64));
+        assert_eq!(four.try_sqrt_ceil().unwrap(), Decimal::from(2u64));
+        assert_eq!(Decimal::zero().try_sqrt_ceil().unwrap(), Decimal::zero());
+
+        let two = Decimal::from(2u64);
+        let floor = two.try_sqrt().unwrap();
+        let ceil = two.try_sqrt_ceil().unwrap();
+        assert_eq!(floor, Decimal::from_scaled_val(1_414_213_562_373_095_048));
+        assert_eq!(ceil, Decimal::from_scaled_val(1_414_213_562_373_095_049));
+        assert!(floor.try_mul_raw(floor).unwrap() < U256::from(two.0) * U256::from(WAD));
+        assert!(ceil.try_mul_raw(ceil).unwrap() >= U256::from(two.0) * U256::from(WAD));
+
+        let max = Decimal(U192::MAX);
+        assert!(max.try_sqrt_ceil().unwrap() > max.try_sqrt().unwrap());
+    }
+
+    #[test]
+    fn test_pack_round_trip() {
+        let values = [
+            Decimal::zero(),
+            Decimal::from_scaled_val(1),
+            Decimal::one(),
+            Decimal::from_percent(33),
+            Decimal::from(u64::MAX),
+            Decimal::from_scaled_val(u128::MAX / 3),
+            Decimal::from_scaled_val(u128::MAX - 1),
+            Decimal::from_scaled_val(u128::MAX),
+        ];
+        for value in values.iter() {
+            let mut packed = vec![0u8; Decimal::LEN];
+            value.pack_into_slice(&mut packed);
+            assert_eq!(Decimal::unpack_from_slice(&packed).unwrap(), *value);
+        }
+        assert_eq!(Decimal::get_packed_len(), 16);
+        assert_eq!(
+            Decimal::one().to_scaled_val().unwrap().to_le_bytes().len(),
+            Decimal::LEN
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "Decimal cannot be packed")]
+    fn test_pack_above_u128_max() {
+        let mut packed = vec![0u8; Decimal::LEN];
+        let value = Decimal(U192::from(u128::MAX) + U192::one());
+        value.pack_into_slice(&mut packed);
+    }
+
+    #[test]
+    fn test_interpolate_log() {
+        let start = Decimal::from(4u64);
+        let end = Decimal::from(16u64);
+        assert_eq!(
+            Decimal::try_interpolate_log(start, end, Rate::zero()).unwrap(),
+            start
+        );
+        assert_close(
+            Decimal::try_interpolate_log(start, end, Rate::one()).unwrap(),
+            end,
+            1_000,
+        );
+        assert_close(
+            Decimal::try_interpolate_log(start, end, Rate::from_percent(50)).unwrap(),
+            start.try_mul(end).unwrap().try_sqrt().unwrap(),
+            1_000,
+        );
+        assert_close(
+            Decimal::try_interpolate_log(end, start, Rate::from_percent(50)).unwrap(),
+            Decimal::from(8u64),
+            1_000,
+        );
+        assert_eq!(
+            Decimal::try_interpolate_log(Decimal::zero(), end, Rate::one()),
+            Err(MathError::DividedByZero.into())
+        );
+    }
+
+    #[test]
+    fn test_power_law_fee() {
+        let amount = Decimal::from(30u64);
+        let scale = Decimal::from_percent(1);
+        assert_close(
+            Decimal::try_power_law_fee(amount, Decimal::one(), scale).unwrap(),
+            amount.try_mul(scale).unwrap(),
+            1_000,
+        );
+        assert_close(
+            Decimal::try_power_law_fee(amount, Decimal::from(2u64), scale).unwrap(),
+            amount.try_mul(amount).unwrap().try_mul(scale).unwrap(),
+            1_000,
+        );
+
+        let sqrt = Decimal::from_percent(50);
+        let fee = Decimal::try_power_law_fee(amount, sqrt, scale).unwrap();
+        let double_fee =
+            Decimal::try_power_law_fee(amount.try_mul(2u64).unwrap(), sqrt, scale).unwrap();
+        assert!(double_fee < fee.try_mul(2u64).unwrap());
+        assert_close(
+            fee,
+            amount.try_sqrt().unwrap().try_mul(scale).unwrap(),
+            1_000,
+        );
+    }
+
+    #[test]
+    fn test_div_pow10() {
+        let value = Decimal::from_scaled_val(123_456_789_012_345_678_901_234_567_890);
+        for n in 0..20u8 {
+            let divisor = Decimal::from(10u64.pow(n as u32));
+            assert_eq!(
+                value.try_div_pow10(n).unwrap(),
+                value.try_div(divisor).unwrap()
+            );
+        }
+        assert_eq!(
+            Decimal(U192::MAX).try_div_pow10(u8::MAX).unwrap(),
+            Decimal::zero()
+        );
+        assert_eq!(Decimal::try_pow10(57).unwrap(), U192::exp10(57));
+        assert_eq!(Decimal::try_pow10(58), Err(MathError::MulOverflow.into()));
+    }
+
+    #[test]
+    fn test_ln_approx_pade() {
+        let values = [
+            Decimal::one(),
+            Decimal::from_scaled_val(1_000_100_000_000_000_000),
+            Decimal::from_scaled_val(1_100_000_000_000_000_000),
+            Decimal::from_scaled_val(1_500_000_000_000_000_000),
+            Decimal::from_scaled_val(1_999_999_999_999_999_999),
+            Decimal::from_scaled_val(2_718_281_828_459_045_235),
+            Decimal::from(1_000_000u64),
+        ];
+        for value in values.iter() {
+            assert_close(
+                value.try_ln_approx_pade().unwrap(),
+                value.try_ln().unwrap(),
+                2_000_000_000,
+            );
+        }
+        assert_eq!(
+            Decimal::from_percent(50).try_ln_approx_pade(),
+            Decimal::from_percent(50).try_ln()
+        );
+        assert_eq!(
+            Decimal::zero().try_ln_approx_pade(),
+            Decimal::zero().try_ln()
+        );
+    }
+
+    #[test]
+    fn test_from_token_price() {
+        // 30,000 USDC (6 decimals) for 1 BTC (8 decimals)
+        assert_eq!(
+            Decimal::from_token_price(30_000_000_000, 100_000_000, 6, 8).unwrap(),
+            Decimal::from(30_000u64)
+        );
+        // 1 BTC (8 decimals) for 30,000 USDC (6 decimals)
+        assert_eq!(
+            Decimal::from_token_price(100_000_000, 30_000_000_000, 8, 6).unwrap(),
+            Decimal::one().try_div(30_000u64).unwrap()
+        );
+        assert_eq!(
+            Decimal::from_token_price(1, 0, 6, 6),
+            Err(MathError::DividedByZero.into())
+        );
+    }
+
+    #[test]
+    fn test_fract_trunc() {
+        let value = Decimal::from_scaled_val(12_345_000_000_000_000_001);
+        assert_eq!(value.trunc(), Decimal::from(12u64));
+        assert_eq!(
+            value.fract(),
+            Decimal::from_scaled_val(345_000_000_000_000_001)
+        );
+        for value in [value, Decimal::zero(), Decimal::one(), Decimal(U192::MAX)].iter() {
+            assert_eq!(value.trunc().try_add(value.fract()).unwrap(), *value);
+            assert!(value.fract() < Decimal::one());
+        }
+    }
+
+    #[test]
+    fn test_to_string_percent_rounded() {
+        assert_eq!(
+            Decimal::from_bips(9999).to_string_percent_rounded(2),
+            "99.99%"
+        );
+        assert_eq!(
+            Decimal::from_bips(10001).to_string_percent_rounded(2),
+            "100.01%"
+        );
+        assert_eq!(
+            Decimal::from_bips(12345).to_string_percent_rounded(1),
+            "123.5%"
+        );
+        assert_eq!(Decimal::zero().to_string_percent_rounded(0), "0%");
+        assert_eq!(
+            Decimal::from_scaled_val(123_456).to_string_percent_rounded(14),
+            "0.00000000001235%"
+        );
+        assert_eq!(
+            Decimal(U192::MAX).to_string_percent_rounded(2),
+            "627710173538668076383578942320766641610235.54%"
+        );
+    }
+
+    #[test]
+    fn test_add_all() {
+        assert_eq!(Decimal::try_add_all(&[]).unwrap(), Decimal::zero());
+        assert_eq!(
+            Decimal::try_add_all(&[1u64, 2, 3].map(Decimal::from)).unwrap(),
+            Decimal::from(6u64)
+        );
+        assert_eq!(
+            Decimal::try_add_all(&[Decimal(U192::MAX), Decimal::one()]),
+            Err(MathError::AddOverflow.into())
+        );
+    }
+
+    #[test]
+    fn test_cross_multiply_cmp() {
+        let [one, two, three, five] = [1u64, 2, 3, 5].map(Decimal::from);
+        assert_eq!(
+            Decimal::try_cross_multiply_cmp(one, three, two, five).unwrap(),
+            Ordering::Less
+        );
+        assert_eq!(
+            Decimal::try_cross_multiply_cmp(two, five, one, three).unwrap(),
+            Ordering::Greater
+        );
+        assert_eq!(
+            Decimal::try_cross_multiply_cmp(one, three, two, Decimal::from(6u64)).unwrap(),
+            Ordering::Equal
+        );
+        assert_eq!(
+            Decimal::try_cross_multiply_cmp(one, Decimal::zero(), two, five),
+            Err(MathError::DividedByZero.into())
+        );
+    }
+
+    #[test]
+    fn test_normalize_oracle_price() {
+        assert_eq!(
+            Decimal::normalize_oracle_price(2_345_678_901_234, -8).unwrap(),
+            Decimal::from_scaled_val(23_456_789_012_340_000_000_000)
+        );
+        assert_eq!(
+            Decimal::normalize_oracle_price(123, 3).unwrap(),
+            Decimal::from(123_000u64)
+        );
+        assert_eq!(
+            Decimal::normalize_oracle_price(123, -20).unwrap(),
+            Decimal::from_scaled_val(1)
+        );
+        assert_eq!(
+            Decimal::normalize_oracle_price(1, i32::MIN).unwrap(),
+            Decimal::zero()
+        );
+        assert_eq!(
+            Decimal::normalize_oracle_price(1, i32::MAX),
+            Err(MathError::MulOverflow.into())
+        );
+    }
+
+    #[test]
+    fn test_annualized_return() {
+        let start = Decimal::one();
+        let end = Decimal::from(2u64);
+        assert_close(
+            Decimal::try_annualized_return(start, end, 365).unwrap(),
+            Decimal::one(),
+            1_000,
+        );
+        // doubling every 73 days compounds 5 times a year
+        assert_close(
+            Decimal::try_annualized_return(start, end, 73).unwrap(),
+            Decimal::from(31u64),
+            100_000,
+        );
+        assert_eq!(
+            Decimal::try_annualized_return(Decimal::zero(), end, 365),
+            Err(MathError::DividedByZero.into())
+        );
+        assert_eq!(
+            Decimal::try_annualized_return(start, end, 0),
+            Err(MathError::DividedByZero.into())
+        );
+        assert_eq!(
+            Decimal::try_annualized_return(end, start, 365),
+            Err(MathError::NegativeValue.into())
+        );
+    }
+
+    #[test]
+    fn test_approx_eq_rel() {
+        let rel_tol = Rate::from_scaled_val(1_000_000_000);
+        for magnitude in [
+            Decimal::from_scaled_val(1_000_000_000_000),
+            Decimal::one(),
+            Decimal::from(u64::MAX),
+        ]
+        .iter()
+        {
+            let within = magnitude
+                .try_add(magnitude.try_div(2_000_000_000u64).unwrap())
+                .unwrap();
+            let outside = magnitude
+                .try_add(magnitude.try_div(500_000_000u64).unwrap())
+                .unwrap();
+            assert!(magnitude.approx_eq_rel(*magnitude, rel_tol).unwrap());
+            assert!(magnitude.approx_eq_rel(within, rel_tol).unwrap());
+            assert!(within.approx_eq_rel(*magnitude, rel_tol).unwrap());
+            assert!(!magnitude.approx_eq_rel(outside, rel_tol).unwrap());
+        }
+        assert!(Decimal::zero()
+            .approx_eq_rel(Decimal::zero(), rel_tol)
+            .unwrap());
+    }
+
+    #[test]
+    fn test_log_return() {
+        let e = Decimal::from_scaled_val(2_718_281_828_459_045_235);
+        assert_close(
+            Decimal::try_log_return(Decimal::one(), e).unwrap(),
+            Decimal::one(),
+            100,
+        );
+        let hundred = Decimal::from(100u64);
+        assert_eq!(
+            Decimal::try_log_return(hundred, hundred).unwrap(),
+            Decimal::zero()
+        );
+        assert_eq!(
+            Decimal::try_log_return(hundred, Decimal::from(90u64)),
+            Err(MathError::NegativeValue.into())
+        );
+    }
+
+    #[test]
+    fn test_kelly_fraction() {
+        let even_odds = Decimal::one();
+        assert_eq!(
+            Decimal::try_kelly_fraction(Rate::from_percent(60), even_odds, even_odds).unwrap(),
+            Decimal::from_percent(20)
+        );
+        assert_eq!(
+            Decimal::try_kelly_fraction(Rate::from_percent(40), even_odds, even_odds),
+            Err(MathError::NegativeValue.into())
+        );
+        assert_eq!(
+            Decimal::try_kelly_fraction(Rate::from_percent(60), even_odds, Decimal::zero()),
+            Err(MathError::DividedByZero.into())
+        );
+    }
+
+    #[test]
+    fn test_harmonic_weighted_average() {
+        let values = [1u64, 4].map(Decimal::from);
+        assert_eq!(
+            Decimal::try_harmonic_weighted_average(&values, &[Decimal::one(); 2]).unwrap(),
+            Decimal::try_harmonic_mean(&values).unwrap()
+        );
+        // 4 / (3 / 1 + 1 / 4)
+        assert_eq!(
+            Decimal::try_harmonic_weighted_average(&values, &[3u64, 1].map(Decimal::from)).unwrap(),
+            Decimal::from(16u64).try_div(13u64).unwrap()
+        );
+        assert_eq!(
+            Decimal::try_harmonic_weighted_average(&[], &[]),
+            Err(MathError::EmptyInput.into())
+        );
+        assert_eq!(
+            Decimal::try_harmonic_weighted_average(&[Decimal::zero()], &[Decimal::one()]),
+            Err(MathError::DividedByZero.into())
+        );
+        assert_eq!(
+            Decimal::try_harmonic_weighted_average(&values, &[Decimal::one()]),
+            Err(MathError::LengthMismatch.into())
+        );
+    }
+
+    #[test]
+    fn test_i128_q() {
+        let one_and_half = Decimal::from_scaled_val(1_500_000_000_000_000_000);
+        assert_eq!(one_and_half.to_i128_q(64).unwrap(), 3 << 63);
+        assert_eq!(Decimal::from_i128_q(3 << 63, 64).unwrap(), one_and_half);
+
+        for value in [
+            Decimal::from_percent(10),
+            Decimal::from_scaled_val(123_456_789_987_654_321_000),
+            Decimal::from(u32::MAX as u64),
+        ]
+        .iter()
+        {
+            for frac_bits in [32, 64, 90].iter() {
+                let q = value.to_i128_q(*frac_bits).unwrap();
+                let round_trip = Decimal::from_i128_q(q, *frac_bits).unwrap();
+                // one unit of q plus one ulp lost when converting back
+                let tolerance = Decimal::from_i128_q(1, *frac_bits)
+                    .unwrap()
+                    .try_add(Decimal::from_scaled_val(1))
+                    .unwrap();
+                assert!(round_trip <= *value);
+                assert!(value.try_sub(round_trip).unwrap() <= tolerance);
+            }
+        }
+
+        assert_eq!(
+            Decimal::from(u64::MAX).to_i128_q(64),
+            Err(MathError::UnableToRoundU128.into())
+        );
+        assert_eq!(
+            Decimal::from_i128_q(-1, 64),
+            Err(MathError::NegativeValue.into())
+        );
+        assert_eq!(
+            Decimal::one().to_i128_q(128),
+            Err(MathError::OutOfRange.into())
+        );
+    }
+
+    #[test]
+    fn test_div_half_even() {
+        let ten = Decimal::from(10u64);
+        let cases = [(5, 0), (15, 2), (25, 2), (35, 4), (26, 3), (24, 2)];
+        for (scaled_val, expected) in cases.iter() {
+            assert_eq!(
+                Decimal::from_scaled_val(*scaled_val)
+                    .try_div_half_even(ten)
+                    .unwrap(),
+                Decimal::from_scaled_val(*expected)
+            );
+        }
+        assert_eq!(
+            Decimal::one().try_div_half_even(Decimal::zero()),
+            Err(MathError::DividedByZero.into())
+        );
+    }
+
+    #[test]
+    fn test_impermanent_loss() {
+        let ratio = Decimal::from(3u64);
+        assert_eq!(
+            Decimal::try_impermanent_loss(ratio, ratio).unwrap(),
+            Decimal::zero()
+        );
+        assert_close(
+            Decimal::try_impermanent_loss(ratio, ratio.try_mul(2u64).unwrap()).unwrap(),
+            Decimal::from_scaled_val(57_190_958_417_936_634),
+            10,
+        );
+        assert_eq!(
+            Decimal::try_impermanent_loss(Decimal::zero(), ratio),
+            Err(MathError::DividedByZero.into())
+        );
+    }
+
+    #[test]
+    fn test_risk_parity_weights() {
+        let half = Decimal::from_percent(50);
+        assert_eq!(
+            Decimal::try_risk_parity_weights(&[Decimal::from_percent(4); 2]).unwrap(),
+            vec![half, half]
+        );
+        let weights =
+            Decimal::try_risk_parity_weights(&[Decimal::from_percent(4), Decimal::from_percent(8)])
+                .unwrap();
+        assert_eq!(weights[1], Decimal::one().try_div(3u64).unwrap());
+        assert_close(weights[0], Decimal::from(2u64).try_div(3u64).unwrap(), 1);
+        assert_eq!(
+            Decimal::try_risk_parity_weights(&[]),
+            Err(MathError::EmptyInput.into())
+        );
+        assert_eq!(
+            Decimal::try_risk_parity_weights(&[Decimal::one(), Decimal::zero()]),
+            Err(MathError::DividedByZero.into())
+        );
+    }
+
+    #[test]
+    fn test_from_str() {
+        let cases = [
+            ("42", Decimal::from(42u64)),
+            ("1.5", Decimal::from_scaled_val(1_500_000_000_000_000_000)),
+            (".25", Decimal::from_percent(25)),
+            ("0.000000000000000001", Decimal::from_scaled_val(1)),
+        ];
+        for (s, expected) in cases.iter() {
+            assert_eq!(Decimal::from_str(s).unwrap(), *expected);
+            assert_eq!(Decimal::from_str(&expected.to_string()).unwrap(), *expected);
+        }
+        for s in [
+            "",
+            ".",
+            "1.2.3",
+            "abc",
+            "-1",
+            "1e5",
+            "0.0000000000000000001",
+        ]
+        .iter()
+        {
+            assert_eq!(Decimal::from_str(s), Err(MathError::UnableToParse.into()));
+        }
+    }
+
+    #[test]
+    fn test_newton() {
+        // x^2 - 2 = 0
+        let two = Decimal::from(2u64);
+        let f = |x: Decimal| {
+            let x_squared = x.try_mul(x)?;
+            if x_squared >= two {
+                Ok((x_squared.try_sub(two)?, false))
+            } else {
+                Ok((two.try_sub(x_squared)?, true))
+            }
+        };
+        let df = |x: Decimal| Ok((x.try_mul(2u64)?, false));
+        assert_close(
+            Decimal::try_newton(Decimal::one(), f, df, 100).unwrap(),
+            two.try_sqrt().unwrap(),
+            1,
+        );
+        assert_eq!(
+            Decimal::try_newton(Decimal::one(), f, df, 1),
+            Err(MathError::DidNotConverge.into())
+        );
+        assert_eq!(
+            Decimal::try_newton(Decimal::zero(), f, df, 100),
+            Err(MathError::DividedByZero.into())
+        );
+    }
+
+    #[test]
+    fn test_from_pyth_price() {
+        assert_eq!(
+            Decimal::from_pyth_price(12_345_678, -5).unwrap(),
+            Decimal::from_scaled_val(123_456_780_000_000_000_000)
+        );
+        assert_eq!(
+            Decimal::from_pyth_price(12, 2).unwrap(),
+            Decimal::from(1_200u64)
+        );
+        assert_eq!(
+            Decimal::from_pyth_price(-1, -5),
+            Err(MathError::NegativeValue.into())
+        );
+    }
+
+    #[test]
+    fn test_time_weighted_average() {
+        let prices = [100u64, 200].map(Decimal::from);
+        assert_eq!(
+            Decimal::try_time_weighted_average(&prices, &[60, 60]).unwrap(),
+            Decimal::from(150u64)
+        );
+        assert_eq!(
+            Decimal::try_time_weighted_average(&prices, &[3, 1]).unwrap(),
+            Decimal::from(125u64)
+        );
+        assert_eq!(
+            Decimal::try_time_weighted_average(&[], &[]),
+            Err(MathError::EmptyInput.into())
+        );
+        assert_eq!(
+            Decimal::try_time_weighted_average(&prices, &[0, 0]),
+            Err(MathError::DividedByZero.into())
+        );
+        assert_eq!(
+            Decimal::try_time_weighted_average(&prices, &[1]),
+            Err(MathError::LengthMismatch.into())
+        );
+    }
+
+    #[test]
+    fn test_percent_bips_of() {
+        let amount = Decimal::from(u64::MAX);
+        assert_eq!(
+            Decimal::percent_of(amount, 50).unwrap(),
+            amount.try_div(2u64).unwrap()
+        );
+        assert_eq!(
+            Decimal::bips_of(amount, 250).unwrap(),
+            amount.try_div(40u64).unwrap()
+        );
+        assert_eq!(
+            Decimal::bips_of(Decimal(U192::MAX), 250),
+            Err(MathError::MulOverflow.into())
+        );
+    }
+
+    #[test]
+    fn test_max_representable() {
+        for decimals in [6u8, 9].iter() {
+            let max = Decimal::max_representable(*decimals);
+            assert_eq!(max.to_atoms(*decimals).unwrap(), u64::MAX);
+            let atom = Decimal::one().try_div_pow10(*decimals).unwrap();
+            assert!(max.try_add(atom).unwrap().to_atoms(*decimals).is_err());
+        }
+        assert_eq!(
+            Decimal::max_representable(6),
+            Decimal::from_scaled_val(18_446_744_073_709_551_615_000_000_000_000)
+        );
+        assert_eq!(
+            Decimal::max_representable(9),
+            Decimal::from_scaled_val(18_446_744_073_709_551_615_000_000_000)
+        );
+    }
+
+    #[test]
+    fn test_volatility() {
+        let returns = [1u8, 3, 1, 3].map(Decimal::from_percent);
+        assert_eq!(
+            Decimal::try_variance(&returns).unwrap(),
+            Decimal::from_bips(1)
+        );
+        assert_eq!(
+            Decimal::try_standard_deviation(&returns).unwrap(),
+            Decimal::from_percent(1)
+        );
+        assert_eq!(
+            Decimal::try_volatility(&returns, 100).unwrap(),
+            Rate::from_percent(10)
+        );
+        assert_eq!(
+            Decimal::try_volatility(&[Decimal::zero(); 5], 365).unwrap(),
+            Rate::zero()
+        );
+        assert_eq!(
+            Decimal::try_volatility(&[], 365),
+            Err(MathError::EmptyInput.into())
+        );
+        assert_eq!(
+            Decimal::try_variance(&[]),
+            Err(MathError::EmptyInput.into())
+        );
+    }
+
+    #[test]
+    fn test_from_scaled_bytes() {
+        let value = Decimal::from_scaled_val(123_456_789_000_000_000_000);
+        assert_eq!(
+            Decimal::try_from_scaled_bytes(&value.to_scaled_val().unwrap().to_le_bytes()).unwrap(),
+            value
+        );
+
+        let wide = Decimal(U192::MAX - U192::from(1u64));
+        let mut bytes = [0u8; 24];
+        wide.0.to_little_endian(&mut bytes);
+        assert_eq!(Decimal::try_from_scaled_bytes(&bytes).unwrap(), wide);
+
+        assert_eq!(
+            Decimal::try_from_scaled_bytes(&bytes[..20]),
+            Err(ProgramError::InvalidArgument)
+        );
+    }
+
+    #[test]
+    fn test_sharpe_ratio() {
+        let stdev = Decimal::from_percent(10);
+        assert_eq!(
+            Decimal::try_sharpe_ratio(Decimal::from_percent(15), Rate::from_percent(5), stdev)
+                .unwrap(),
+            Decimal::one()
+        );
+        assert_eq!(
+            Decimal::try_sharpe_ratio(Decimal::from_percent(4), Rate::from_percent(5), stdev),
+            Err(MathError::NegativeValue.into())
+        );
+        assert_eq!(
+            Decimal::try_sharpe_ratio(
+                Decimal::from_percent(15),
+                Rate::from_percent(5),
+                Decimal::zero()
+            ),
+            Err(MathError::DividedByZero.into())
+        );
+    }
+
+    #[test]
+    fn test_max_drawdown() {
+        assert_eq!(
+            Decimal::try_max_drawdown(&[100u64, 90, 80, 90, 100].map(Decimal::from)).unwrap(),
+            Rate::from_percent(20)
+        );
+        assert_eq!(
+            Decimal::try_max_drawdown(&[1u64, 2, 3].map(Decimal::from)).unwrap(),
+            Rate::zero()
+        );
+        assert_eq!(
+            Decimal::try_max_drawdown(&[100u64, 50, 200, 150].map(Decimal::from)).unwrap(),
+            Rate::from_percent(50)
+        );
+        assert_eq!(
+            Decimal::try_max_drawdown(&[]),
+            Err(MathError::EmptyInput.into())
+        );
+    }
+
+    #[test]
+    fn test_accrue_capped() {
+        let amount = Decimal::from(100u64);
+        let rate = Rate::from_percent(10);
+        let max_factor = Decimal::from(2u64);
+        assert_eq!(
+            Decimal::accrue_capped(amount, rate, 2, max_factor).unwrap(),
+            Decimal::from(121u64)
+        );
+        assert_eq!(
+            Decimal::accrue_capped(amount, rate, 10, max_factor).unwrap(),
+            Decimal::from(200u64)
+        );
+        assert_eq!(
+            Decimal::accrue_capped(amount, rate, 0, max_factor).unwrap(),
+            amount
+        );
+        // A cap below one applies even when nothing accrues
+        let half = Decimal::from_percent(50);
+        assert_eq!(
+            Decimal::accrue_capped(amount, rate, 0, half).unwrap(),
+            Decimal::from(50u64)
+        );
+        assert_eq!(
+            Decimal::accrue_capped(amount, Rate::zero(), 1, half).unwrap(),
+            Decimal::from(50u64)
+        );
+        // 1.1^33 no longer fits a Rate
+        assert_eq!(
+            Decimal::accrue_capped(amount, rate, 33, max_factor).unwrap(),
+            Decimal::from(200u64)
+        );
+        // 1.1^u64::MAX does not fit a Decimal either
+        assert_eq!(
+            Decimal::accrue_capped(amount, rate, u64::MAX, Decimal::from(1_000u64)).unwrap(),
+            Decimal::from(100_000u64)
+        );
+        assert_close(
+            Decimal::accrue_capped(amount, rate, 40, Decimal::from(1_000u64)).unwrap(),
+            Decimal::from_scaled_val(4_525_925_556_817_595_180_588),
+            1_000,
+        );
+    }
+
+    #[test]
+    fn test_convexity() {
+        let rate = Rate::from_percent(5);
+        let zero_coupon = [Decimal::zero(), Decimal::zero(), Decimal::from(1_000u64)];
+        let expected = Decimal::from(12u64)
+            .try_div(Rate::one().try_add(rate).unwrap().try_pow(2).unwrap())
+            .unwrap();
+        assert_close(
+            Decimal::try_convexity(&zero_coupon, rate).unwrap(),
+            expected,
+            1_000,
+        );
+        assert_eq!(
+            Decimal::try_convexity(&[], rate),
+            Err(MathError::EmptyInput.into())
+        );
+        assert_eq!(
+            Decimal::try_convexity(&[Decimal::zero()], rate),
+            Err(MathError::DividedByZero.into())
+        );
+        // 1.05^202 is past the largest Rate
+        assert_close(
+            Decimal::try_convexity(&[Decimal::from(100u64); 200], rate).unwrap(),
+            Decimal::from_scaled_val(797_450_688_105_137_634_850),
+            1_000_000,
+        );
+    }
+
+    #[test]
+    fn test_sub_or_deficit() {
+        let [three, five] = [3u64, 5].map(Decimal::from);
+        let two = Decimal::from(2u64);
+        assert_eq!(five.try_sub_or_deficit(three), (two, Decimal::zero()));
+        assert_eq!(three.try_sub_or_deficit(five), (Decimal::zero(), two));
+        assert_eq!(
+            three.try_sub_or_deficit(three),
+            (Decimal::zero(), Decimal::zero())
+        );
+    }
+
+    #[test]
+    fn test_geometric_series_sum() {
+        let first = Decimal::from(4u64);
+        assert_eq!(
+            Decimal::geometric_series_sum(first, Rate::from_percent(50), 3).unwrap(),
+            Decimal::from(7u64)
+        );
+        assert_eq!(
+            Decimal::geometric_series_sum(first, Rate::from_percent(200), 4).unwrap(),
+            Decimal::from(60u64)
+        );
+        assert_eq!(
+            Decimal::geometric_series_sum(first, Rate::one(), 5).unwrap(),
+            Decimal::from(20u64)
+        );
+        assert_eq!(
+            Decimal::geometric_series_sum(first, Rate::from_percent(50), 0).unwrap(),
+            Decimal::zero()
+        );
+        // 1 + 0.9 + 0.81 + 0.729
+        assert_eq!(
+            Decimal::geometric_series_sum(Decimal::one(), Rate::from_percent(90), 4).unwrap(),
+            Decimal::from_scaled_val(3_439_000_000_000_000_000)
+        );
+        let ratio = Rate::from_percent(105);
+        assert_close(
+            Decimal::geometric_series_sum(Decimal::one(), ratio, 100).unwrap(),
+            Decimal::from_scaled_val(2_610_025_156_926_069_100_511),
+            1_000_000,
+        );
+        // 1.05^150 is past the largest Rate, but the sum fits a Decimal
+        assert_close(
+            Decimal::geometric_series_sum(Decimal::one(), ratio, 150).unwrap(),
+            Decimal::from_scaled_val(30_139_549_921_060_782_995_937),
+            10_000_000,
+        );
+    }
+
+    #[test]
+    fn test_duration() {
+        let cashflows = [Decimal::from(100u64); 2];
+        // (1.1 + 2) / (1.1 + 1) = 31 / 21 for a two-period annuity at 10%
+        assert_close(
+            Decimal::try_duration(&cashflows, &[1, 2], Rate::from_percent(10)).unwrap(),
+            Decimal::from(31u64).try_div(21u64).unwrap(),
+            1_000,
+        );
+        assert_eq!(
+            Decimal::try_duration(&cashflows, &[1, 2], Rate::zero()).unwrap(),
+            Decimal::from_scaled_val(1_500_000_000_000_000_000)
+        );
+        assert_eq!(
+            Decimal::try_duration(&[], &[], Rate::from_percent(10)),
+            Err(MathError::EmptyInput.into())
+        );
+        assert_eq!(
+            Decimal::try_duration(&cashflows, &[1], Rate::from_percent(10)),
+            Err(MathError::LengthMismatch.into())
+        );
+
+        let rate = Rate::from_percent(5);
+        let times = (1..=100).collect::<Vec<u64>>();
+        assert_close(
+            Decimal::try_duration(&[Decimal::from(100u64); 100], &times, rate).unwrap(),
+            Decimal::from_scaled_val(20_233_723_860_977_845_937),
+            1_000_000,
+        );
+        // 1.05^200 is past the largest Rate
+        let times = (1..=200).collect::<Vec<u64>>();
+        assert_close(
+            Decimal::try_duration(&[Decimal::from(100u64); 200], &times, rate).unwrap(),
+            Decimal::from_scaled_val(20_988_433_677_514_050_380),
+            1_000_000,
+        );
+    }
+
+    #[test]
+    fn test_correlation() {
+        let x = [1u64, 2, 3].map(Decimal::from);
+        assert_close(
+            Decimal::try_correlation(&x, &[2u64, 4, 6].map(Decimal::from)).unwrap(),
+            Decimal::one(),
+            10,
+        );
+        assert_eq!(
+            Decimal::try_correlation(&x, &[1u64, 3, 1].map(Decimal::from)).unwrap(),
+            Decimal::zero()
+        );
+        assert_eq!(
+            Decimal::try_correlation(&x, &[3u64, 2, 1].map(Decimal::from)),
+            Err(MathError::NegativeValue.into())
+        );
+        assert_eq!(
+            Decimal::try_correlation(&x, &[Decimal::one(); 3]),
+            Err(MathError::DividedByZero.into())
+        );
+        assert_eq!(
+            Decimal::try_correlation(&x, &x[1..]),
+            Err(MathError::LengthMismatch.into())
+        );
+        assert_eq!(
+            Decimal::try_correlation(&[], &[]),
+            Err(MathError::EmptyInput.into())
+        );
+    }
+
+    #[test]
+    fn test_round_to() {
+        let value = Decimal::from_scaled_val(1_250_000_000_000_000_000);
+        assert_eq!(value.round_to(2).unwrap(), (value, false));
+        assert_eq!(value.round_to(18).unwrap(), (value, false));
+        assert_eq!(
+            value.round_to(1).unwrap(),
+            (Decimal::from_scaled_val(1_300_000_000_000_000_000), true)
+        );
+        assert_eq!(value.round_to(0).unwrap(), (Decimal::one(), true));
+        assert_eq!(
+            Decimal::from_scaled_val(1_240_000_000_000_000_001)
+                .round_to(1)
+                .unwrap(),
+            (Decimal::from_scaled_val(1_200_000_000_000_000_000), true)
+        );
+    }
+
+    #[test]
+    fn test_default() {
+        assert_eq!(Decimal::default(), Decimal::zero());
+    }
+
+    #[test]
+    fn test_floor_div_u64() {
+        assert_eq!(Decimal::from(10u64).try_floor_div_u64(3), Ok(3));
+        assert_eq!(
+            Decimal::from_scaled_val(8_999_999_999_999_999_999).try_floor_div_u64(3),
+            Ok(2)
+        );
+        assert_eq!(
+            Decimal::from(10u64).try_floor_div_u64(0),
+            Err(MathError::DividedByZero.into())
+        );
+        assert_eq!(
+            Decimal::from(u64::MAX)
+                .try_mul(4u64)
+                .unwrap()
+                .try_floor_div_u64(2),
+            Err(MathError::UnableToRoundU64.into())
+        );
+    }
+
+    #[test]
+    fn test_div_guarded() {
+        let min_divisor = Decimal::from_scaled_val(1_000_000);
+        assert_eq!(
+            Decimal::one().try_div_guarded(min_divisor, min_divisor),
+            Ok(Decimal::from(1_000_000_000_000u64))
+        );
+        assert_eq!(
+            Decimal::one().try_div_guarded(Decimal::from_scaled_val(999_999), min_divisor),
+            Err(MathError::DivisorTooSmall.into())
+        );
+        assert_eq!(
+            Decimal::one().try_div_guarded(Decimal::zero(), Decimal::zero()),
+            Err(MathError::DividedByZero.into())
+        );
+    }
+
+    #[test]
+    fn test_align_scales() {
+        assert_eq!(
+            Decimal::align_scales(Decimal::from(15u64), 6, Decimal::from(2u64), 9),
+            Ok((Decimal::from(15_000u64), Decimal::from(2u64), 9))
+        );
+        assert_eq!(
+            Decimal::align_scales(Decimal::from(15u64), 9, Decimal::from(2u64), 6),
+            Ok((Decimal::from(15u64), Decimal::from(2_000u64), 9))
+        );
+        assert_eq!(
+            Decimal::align_scales(Decimal::from(15u64), 6, Decimal::from(2u64), 6),
+            Ok((Decimal::from(15u64), Decimal::from(2u64), 6))
+        );
+        assert_eq!(
+            Decimal::align_scales(Decimal::from(u64::MAX), 0, Decimal::one(), 40),
+            Err(MathError::MulOverflow.into())
+        );
+    }
+
+    #[test]
+    fn test_ceil_div_u64() {
+        assert_eq!(Decimal::from(10u64).try_ceil_div_u64(3), Ok(4));
+        assert_eq!(Decimal::from(9u64).try_ceil_div_u64(3), Ok(3));
+        assert_eq!(
+            Decimal::from_scaled_val(9_000_000_000_000_000_001).try_ceil_div_u64(3),
+            Ok(4)
+        );
+        assert_eq!(
+            Decimal::from(10u64).try_ceil_div_u64(0),
+            Err(MathError::DividedByZero.into())
+        );
+    }
+
+    #[test]
+    fn test_round_nearest_u64() {
+        assert_eq!(Decimal::from(150u64).try_round_nearest_u64(100), Ok(200));
+        assert_eq!(Decimal::from(149u64).try_round_nearest_u64(100), Ok(100));
+        assert_eq!(Decimal::from(49u64).try_round_nearest_u64(100), Ok(0));
+        assert_eq!(
+            Decimal::from(150u64).try_round_nearest_u64(0),
+            Err(MathError::DividedByZero.into())
+        );
+    }
+
+    #[test]
+    fn test_clamp_to_rate() {
+        assert_eq!(Decimal::from(2u64).try_clamp_to_rate(), Ok(Rate::one()));
+        assert_eq!(
+            Decimal::from_percent(50).try_clamp_to_rate(),
+            Ok(Rate::from_percent(50))
+        );
+        assert_eq!(Decimal::zero().try_clamp_to_rate(), Ok(Rate::zero()));
+    }
+
+    #[test]
+    fn test_to_atoms_exact() {
+        let amount = Decimal::from_scaled_val(1_234_567_000_000_000_000);
+        assert_eq!(amount.to_atoms_exact(6), Ok((1_234_567, false)));
+        assert_eq!(amount.to_atoms_exact(9), Ok((1_234_567_000, false)));
+        assert_eq!(amount.to_atoms_exact(3), Ok((1_234, true)));
+        assert_eq!(
+            Decimal::from(u64::MAX).to_atoms_exact(1),
+            Err(MathError::UnableToRoundU64.into())
+        );
+    }
+
+    #[test]
+    fn test_min_max_of() {
+        let values = [3u64, 1, 2].map(Decimal::from);
+        assert_eq!(Decimal::min_of(&values), Some(Decimal::one()));
+        assert_eq!(Decimal::max_of(&values), Some(Decimal::from(3u64)));
+        assert_eq!(Decimal::min_of(&[]), None);
+        assert_eq!(Decimal::max_of(&[]), None);
+    }
+
+    #[test]
+    fn test_ln_e_pi() {
+        assert_close(Decimal::ln_e().try_ln().unwrap(), Decimal::one(), 100);
+        assert_close(Decimal::one().try_exp().unwrap(), Decimal::ln_e(), 100);
+        assert_eq!(Decimal::pi().to_string(), "3.141592653589793238");
+    }
+
+    #[test]
+    fn test_truncate_to_decimals() {
+        assert_eq!(
+            Decimal::from_scaled_val(WAD as u128 + 999).try_truncate_to_decimals(0),
+            Ok(Decimal::one())
+        );
+        assert_eq!(
+            Decimal::from_scaled_val(1_999_999_999_999_999_999).try_truncate_to_decimals(6),
+            Ok(Decimal::from_scaled_val(1_999_999_000_000_000_000))
+        );
+        let value = Decimal::from_scaled_val(1_234);
+        assert_eq!(value.try_truncate_to_decimals(18), Ok(value));
+        assert_eq!(
+            value.try_truncate_to_decimals(19),
+            Err(MathError::OutOfRange.into())
+        );
+    }
+
+    #[test]
+    fn test_clamp_reporting() {
+        let lo = Decimal::one();
+        let hi = Decimal::from(10u64);
+        assert_eq!(
+            Decimal::from(5u64).clamp_reporting(lo, hi),
+            (Decimal::from(5u64), false)
+        );
+        assert_eq!(hi.clamp_reporting(lo, hi), (hi, false));
+        assert_eq!(Decimal::zero().clamp_reporting(lo, hi), (lo, true));
+        assert_eq!(Decimal::from(11u64).clamp_reporting(lo, hi), (hi, true));
+    }
+
+    #[test]
+    fn test_pow_mod() {
+        let modulus = Decimal::from(1000u64);
+        assert_eq!(
+            Decimal::from(2u64).try_pow_mod(10, modulus),
+            Ok(Decimal::from(24u64))
+        );
+        assert_eq!(
+            Decimal::from(7u64).try_pow_mod(0, modulus),
+            Ok(Decimal::one())
+        );
+        assert_eq!(
+            Decimal::from(3u64).try_pow_mod(200, Decimal::from(13u64)),
+            Ok(Decimal::from(9u64))
+        );
+        assert_eq!(
+            Decimal::from(2u64).try_pow_mod(10, Decimal::zero()),
+            Err(MathError::DividedByZero.into())
+        );
+        let modulus = Decimal::from(100_000_000_000u64);
+        let value = Decimal::from(99_999_999_999u64);
+        assert_eq!(value.try_pow_mod(1, modulus), Ok(value));
+        // Fermat's little theorem for the Mersenne primes 2^61 - 1 and 2^127 - 1
+        let prime = Decimal::from((1u64 << 61) - 1);
+        assert_eq!(
+            Decimal::from(3u64).try_pow_mod((1 << 61) - 2, prime),
+            Ok(Decimal::one())
+        );
+        let prime = Decimal(U192::from(u128::MAX >> 1) * U192::from(WAD));
+        assert_eq!(
+            Decimal::from(5u64).try_pow_mod(1_000, prime),
+            Ok(Decimal(
+                U192::from(151_829_391_582_588_611_779_559_923_393_586_176_891u128)
+                    * U192::from(WAD)
+            ))
+        );
+    }
+
+    #[test]
+    fn test_div_terminating() {
+        assert_eq!(
+            Decimal::one().try_div_terminating(Decimal::from(4u64)),
+            Ok((Decimal::from_percent(25), true))
+        );
+        assert_eq!(
+            Decimal::one().try_div_terminating(Decimal::from(3u64)),
+            Ok((Decimal::from_scaled_val(333_333_333_333_333_333), false))
+        );
+        assert_eq!(
+            Decimal::one().try_div_terminating(Decimal::zero()),
+            Err(MathError::DividedByZero.into())
+        );
+    }
+
+    #[test]
+    fn test_gcd_lcm() {
+        let a = Decimal::from(12u64);
+        let b = Decimal::from(8u64);
+        assert!(a.is_integer());
+        assert!(!Decimal::from_percent(50).is_integer());
+        assert_eq!(a.try_gcd(b), Ok(Decimal::from(4u64)));
+        assert_eq!(a.try_lcm(b), Ok(Decimal::from(24u64)));
+        assert_eq!(a.try_gcd(Decimal::zero()), Ok(a));
+        assert_eq!(
+            Decimal::zero().try_lcm(Decimal::zero()),
+            Ok(Decimal::zero())
+        );
+        assert_eq!(
+            a.try_gcd(Decimal::from_percent(50)),
+            Err(MathError::OutOfRange.into())
+        );
+    }
+
+    #[test]
+    fn test_from_str_display_round_trip() {
+        // xorshift64, so the sampled values are reproducible
+        let mut state = 0x2545_f491_4f6c_dd1du64;
+        let mut next = || {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            state
+        };
+        let mut values = vec![
+            Decimal::zero(),
+            Decimal::from_scaled_val(1),
+            Decimal::from_scaled_val(WAD as u128 - 1),
+            Decimal::one(),
+            Decimal::from_scaled_val(WAD as u128 * 10),
+            Decimal(U192::MAX),
+        ];
+        for _ in 0..1_000 {
+            let words = [next(), next(), next()];
+            // Shift by a random amount so short and long values are both hit
+            values.push(Decimal(U192(words) >> (next() % 192) as usize));
+        }
+        for value in values {
+            assert_eq!(Decimal::from_str(&value.to_string()), Ok(value));
+        }
+    }
+
+    #[test]
+    fn test_log_base() {
+        let two = Decimal::from(2u64);
+        assert_close(
+            Decimal::from(8u64).try_log_base(two).unwrap(),
+            Decimal::from(3u64),
+            100,
+        );
+        assert_close(
+            Decimal::from(1000u64)
+                .try_log_base(Decimal::from(10u64))
+                .unwrap(),
+            Decimal::from(3u64),
+            100,
+        );
+        assert_close(
+            Decimal::from_percent(25)
+                .try_log_base(Decimal::from_percent(50))
+                .unwrap(),
+            two,
+            100,
+        );
+        assert_eq!(Decimal::one().try_log_base(two), Ok(Decimal::zero()));
+        assert_eq!(
+            Decimal::from_percent(50).try_log_base(two),
+            Err(MathError::NegativeValue.into())
+        );
+        assert_eq!(
+            Decimal::from(8u64).try_log_base(Decimal::one()),
+            Err(MathError::OutOfRange.into())
+        );
+        assert_eq!(
+            Decimal::from(8u64).try_log_base(Decimal::zero()),
+            Err(MathError::NegativeValue.into())
+        );
+    }
+
+    #[test]
+    fn test_continued_fraction_approx() {
+        assert_eq!(
+            Decimal::from_percent(33).try_continued_fraction_approx(10),
+            Ok((1, 3))
+        );
+        assert_eq!(
+            Decimal::from_percent(33).try_continued_fraction_approx(100),
+            Ok((33, 100))
+        );
+        assert_eq!(Decimal::pi().try_continued_fraction_approx(10), Ok((22, 7)));
+        assert_eq!(
+            Decimal::pi().try_continued_fraction_approx(1_000),
+            Ok((355, 113))
+        );
+        assert_eq!(
+            Decimal::from(5u64).try_continued_fraction_approx(1),
+            Ok((5, 1))
+        );
+        assert_eq!(
+            Decimal::one().try_continued_fraction_approx(0),
+            Err(MathError::DividedByZero.into())
+        );
+    }
+
+    #[test]
+    fn test_mul_div_u128() {
+        let max = u128::MAX;
+        assert_eq!(
+            Decimal::mul_div_u128(max, max - 1, max, RoundingMode::Floor),
+            Ok(max - 1)
+        );
+        assert_eq!(
+            Decimal::mul_div_u128(max, 3, 7, RoundingMode::Floor),
+            Ok(145_835_300_108_973_627_198_589_117_470_757_804_909)
+        );
+        assert_eq!(
+            Decimal::mul_div_u128(max, 3, 7, RoundingMode::Ceil),
+            Ok(145_835_300_108_973_627_198_589_117_470_757_804_910)
+        );
+        assert_eq!(Decimal::mul_div_u128(5, 1, 2, RoundingMode::HalfUp), Ok(3));
+        assert_eq!(
+            Decimal::mul_div_u128(5, 1, 2, RoundingMode::HalfEven),
+            Ok(2)
+        );
+        assert_eq!(
+            Decimal::mul_div_u128(1, 1, 0, RoundingMode::Floor),
+            Err(MathError::DividedByZero.into())
+        );
+        assert_eq!(
+            Decimal::mul_div_u128(max, 2, 1, RoundingMode::Floor),
+            Err(MathError::UnableToRoundU128.into())
+        );
+    }
+
+    #[test]
+    fn test_rate_compact() {
+        for value in [
+            Decimal::zero(),
+            Decimal::from_percent(5),
+            Decimal::one(),
+            Decimal::from_scaled_val(u64::MAX as u128),
+        ] {
+            let compact = value.to_rate_compact().unwrap();
+            assert_eq!(Decimal::from_rate_compact(compact), value);
+        }
+        assert_eq!(
+            Decimal::from_percent(5).to_rate_compact(),
+            Ok(Rate::from_percent(5).to_scaled_val() as u64)
+        );
+        assert_eq!(
+            Decimal::from(19u64).to_rate_compact(),
+            Err(MathError::UnableToRoundU64.into())
+        );
+    }
+
+    #[test]
+    fn test_compound_interest_continuous() {
+        let principal = Decimal::from(100u64);
+        assert_close(
+            principal
+                .try_compound_interest_continuous(Rate::from_percent(10), Decimal::one())
+                .unwrap(),
+            Decimal::from_scaled_val(110_517_091_807_564_762_481),
+            100_000,
+        );
+        assert_close(
+            principal
+                .try_compound_interest_continuous(Rate::from_percent(5), Decimal::from(5u64))
+                .unwrap(),
+            Decimal::from_scaled_val(128_402_541_668_774_148_407),
+            100_000,
+        );
+        assert_eq!(
+            principal.try_compound_interest_continuous(Rate::from_percent(10), Decimal::zero()),
+            Ok(principal)
+        );
+    }
+
+    #[test]
+    fn test_arithmetic_mean() {
+        assert_eq!(
+            Decimal::try_arithmetic_mean(&[1u64, 2, 3].map(Decimal::from)),
+            Ok(Decimal::from(2u64))
+        );
+        assert_eq!(
+            Decimal::try_arithmetic_mean(&[Decimal::one()]),
+            Ok(Decimal::one())
+        );
+        assert_eq!(
+            Decimal::try_arithmetic_mean(&[]),
+            Err(MathError::EmptyInput.into())
+        );
+    }
+
+    #[test]
+    fn test_div_to_scaled_u64() {
+        let quote = Decimal::from(100u64);
+        let base = Decimal::from(3u64);
+        assert_eq!(
+            quote.div_to_scaled_u64(base, 8, RoundingMode::Floor),
+            Ok(3_333_333_333)
+        );
+        assert_eq!(
+            quote.div_to_scaled_u64(base, 8, RoundingMode::Ceil),
+            Ok(3_333_333_334)
+        );
+        assert_eq!(
+            Decimal::from(200u64).div_to_scaled_u64(base, 8, RoundingMode::HalfUp),
+            Ok(6_666_666_667)
+        );
+        assert_eq!(
+            quote.div_to_scaled_u64(Decimal::zero(), 8, RoundingMode::Floor),
+            Err(MathError::DividedByZero.into())
+        );
+        assert_eq!(
+            Decimal::from(u64::MAX).div_to_scaled_u64(Decimal::one(), 1, RoundingMode::Floor),
+            Err(MathError::UnableToRoundU64.into())
+        );
+        assert_eq!(
+            Decimal(U192::MAX).div_to_scaled_u64(Decimal(U192::MAX), 57, RoundingMode::Floor),
+            Err(MathError::MulOverflow.into())
+        );
+    }
+
+    #[test]
+    fn test_fold_add() {
+        let mut acc = Decimal::one();
+        assert_eq!(
+            Decimal::try_fold_add(&mut acc, &[Decimal::one(), Decimal::from(2u64)]),
+            Ok(())
+        );
+        assert_eq!(acc, Decimal::from(4u64));
+
+        let mut acc = Decimal::zero();
+        assert_eq!(
+            Decimal::try_fold_add(
+                &mut acc,
+                &[Decimal::one(), Decimal(U192::MAX), Decimal::one()]
+            ),
+            Err((MathError::AddOverflow.into(), 1))
+        );
+        assert_eq!(acc, Decimal::one());
+    }
+
+    #[test]
+    fn test_ratio_to_percentage() {
+        assert_eq!(
+            Decimal::from_bips(125).try_ratio_to_percentage(2),
+            Ok("1.25".to_string())
+        );
+        assert_eq!(
+            Decimal::from_percent(5).try_ratio_to_percentage(2),
+            Ok("5.00".to_string())
+        );
+        assert_eq!(
+            Decimal::from_scaled_val(123_456_789_000_000_000).try_ratio_to_percentage(1),
+            Ok("12.3".to_string())
+        );
+        assert_eq!(
+            Decimal::from_bips(125).try_ratio_to_percentage(0),
+            Ok("1".to_string())
+        );
+        assert_eq!(
+            Decimal(U192::MAX).try_ratio_to_percentage(2),
+            Err(MathError::MulOverflow.into())
+        );
+    }
+
+    #[test]
+    fn test_sigmoid() {
+        let k = Decimal::one();
+        assert_eq!(
+            Decimal::zero().try_sigmoid(k),
+            Ok(Decimal::from_percent(50))
+        );
+        assert_close(
+            Decimal::one().try_sigmoid(k).unwrap(),
+            Decimal::from_scaled_val(731_058_578_630_004_879),
+            100,
+        );
+        assert_close(
+            Decimal::one().try_sigmoid(Decimal::from(2u64)).unwrap(),
+            Decimal::from_scaled_val(880_797_077_977_882_444),
+            100,
+        );
+        assert_close(
+            Decimal::from(100u64).try_sigmoid(k).unwrap(),
+            Decimal::one(),
+            1,
+        );
+        let mut previous = Decimal::zero();
+        for x in 0..20u64 {
+            let y = Decimal::from(x).try_sigmoid(k).unwrap();
+            assert!(y > previous || y == Decimal::one());
+            previous = y;
+        }
+    }
+
+    #[test]
+    fn test_from_rational_u128() {
+        let max = u128::MAX;
+        assert_eq!(Decimal::from_rational_u128(max, max), Ok(Decimal::one()));
+        assert_eq!(
+            Decimal::from_rational_u128(max, max / 3),
+            Ok(Decimal::from(3u64))
+        );
+        assert_eq!(
+            Decimal::from_rational_u128(10u128.pow(38), 3 * 10u128.pow(37)),
+            Ok(Decimal::from_scaled_val(3_333_333_333_333_333_333))
+        );
+        assert_eq!(
+            Decimal::from_rational_u128(max, 1),
+            Ok(Decimal(U192::from(max) * U192::from(WAD)))
+        );
+        assert_eq!(
+            Decimal::from_rational_u128(1, 0),
+            Err(MathError::DividedByZero.into())
+        );
+    }
+
+    #[test]
+    fn test_mul_pow10() {
+        let price = Decimal::from(12_345u64);
+        let amount = Decimal::from(2u64);
+        assert_eq!(
+            price.try_mul_pow10(amount, -2),
+            Ok(Decimal::from_scaled_val(246_900_000_000_000_000_000))
+        );
+        assert_eq!(
+            price.try_mul_pow10(amount, 3),
+            Ok(Decimal::from(24_690_000u64))
+        );
+        assert_eq!(price.try_mul_pow10(amount, 0), Ok(Decimal::from(24_690u64)));
+        assert_eq!(
+            Decimal::from_scaled_val(1).try_mul_pow10(Decimal::one(), -1),
+            Ok(Decimal::zero())
+        );
+        assert_eq!(price.try_mul_pow10(amount, -100), Ok(Decimal::zero()));
+        // The fused product keeps digits a separate multiply would truncate
+        let tiny = Decimal::from_scaled_val(5);
+        assert_eq!(
+            tiny.try_mul_pow10(Decimal::from_scaled_val(500_000_000_000_000_000), 18),
+            Ok(Decimal::from_scaled_val(2_500_000_000_000_000_000))
+        );
+        assert_eq!(
+            Decimal(U192::MAX).try_mul_pow10(Decimal::one(), 1),
+            Err(MathError::MulOverflow.into())
+        );
+        assert_eq!(
+            Decimal(U192::MAX).try_mul_pow10(Decimal(U192::MAX), 0),
+            Err(MathError::MulOverflow.into())
+        );
+    }
+
+    #[test]
+    fn test_softmax() {
+        let third = Decimal::from_scaled_val(333_333_333_333_333_333);
+        assert_eq!(
+            Decimal::try_softmax(&[Decimal::one(); 3]),
+            Ok(vec![third; 3])
+        );
+        let weights = Decimal::try_softmax(&[Decimal::zero(), Decimal::one()]).unwrap();
+        assert_close(
+            weights[0],
+            Decimal::from_scaled_val(268_941_421_369_995_120),
+            100,
+        );
+        assert_close(
+            weights[1],
+            Decimal::from_scaled_val(731_058_578_630_004_879),
+            100,
+        );
+        // Shifting by the maximum keeps e^1000 from overflowing
+        let weights =
+            Decimal::try_softmax(&[Decimal::from(1_000u64), Decimal::from(1_000u64)]).unwrap();
+        assert_eq!(weights, vec![Decimal::from_percent(50); 2]);
+        assert_eq!(Decimal::try_softmax(&[]), Err(MathError::EmptyInput.into()));
+    }
+
+    #[test]
+    fn test_div_pow10_lossy() {
+        assert_eq!(
+            Decimal::from(5u64).try_div_pow10_lossy(2),
+            Ok((Decimal::from_percent(5), false))
+        );
+        assert_eq!(
+            Decimal::from_scaled_val(5).try_div_pow10_lossy(1),
+            Ok((Decimal::zero(), true))
+        );
+        assert_eq!(
+            Decimal::from_scaled_val(105).try_div_pow10_lossy(1),
+            Ok((Decimal::from_scaled_val(10), true))
+        );
+        assert_eq!(
+            Decimal::one().try_div_pow10_lossy(200),
+            Ok((Decimal::zero(), true))
+        );
+        assert_eq!(
+            Decimal::zero().try_div_pow10_lossy(200),
+            Ok((Decimal::zero(), false))
+        );
+    }
+
+    #[test]
+    fn test_gbm_step() {
+        let price = Decimal::from(100u64);
+        let dt = Decimal::one();
+        assert_eq!(
+            price.try_gbm_step(Rate::zero(), Rate::zero(), dt, Decimal::one()),
+            Ok(price)
+        );
+        let drift = Rate::from_percent(5);
+        let volatility = Rate::from_percent(20);
+        assert_close(
+            price
+                .try_gbm_step(drift, volatility, dt, Decimal::zero())
+                .unwrap(),
+            Decimal::from_scaled_val(103_045_453_395_351_685_561),
+            100_000,
+        );
+        assert_close(
+            price
+                .try_gbm_step(drift, volatility, dt, Decimal::one())
+                .unwrap(),
+            Decimal::from_scaled_val(125_860_000_992_947_786_281),
+            100_000,
+        );
+        // Volatility drag outweighs zero drift
+        assert_close(
+            price
+                .try_gbm_step(Rate::zero(), volatility, dt, Decimal::zero())
+                .unwrap(),
+            Decimal::from_scaled_val(98_019_867_330_675_530_222),
+            100_000,
+        );
+    }
+
+    #[test]
+    fn test_sub_underflow() {
+        assert_eq!(
+            Decimal::zero().try_sub(Decimal::from_percent(1)),
+            Err(MathError::SubUnderflow.into())
+        );
     }
 }
@@ -1,21 +1,39 @@
+#![allow(non_local_definitions)]
+
 use num_derive::FromPrimitive;
 use solana_program::{decode_error::DecodeError, program_error::ProgramError};
 use thiserror::Error;
 
 #[derive(Clone, Debug, Eq, Error, FromPrimitive, PartialEq)]
 pub enum MathError {
-    #[error("AddOverflow")]
+    #[error("AddOverflow: result exceeds the maximum value")]
     AddOverflow,
-    #[error("Underflow")]
+    #[error("SubUnderflow: result would be negative")]
     SubUnderflow,
-    #[error("MulOverflow")]
+    #[error("MulOverflow: result exceeds the maximum value")]
     MulOverflow,
-    #[error("DividedByZero")]
+    #[error("DividedByZero: attempted division by zero")]
     DividedByZero,
-    #[error("UnableToRoundU64")]
+    #[error("UnableToRoundU64: result does not fit in u64")]
     UnableToRoundU64,
-    #[error("UnableToRoundU128")]
+    #[error("UnableToRoundU128: result does not fit in u128")]
     UnableToRoundU128,
+    #[error("LengthMismatch: input slices have different lengths")]
+    LengthMismatch,
+    #[error("NegativeValue: result is not representable as an unsigned decimal")]
+    NegativeValue,
+    #[error("OutOfRange: value is outside the allowed range")]
+    OutOfRange,
+    #[error("ArithmeticError: arithmetic operation failed")]
+    ArithmeticError,
+    #[error("UnableToParse: string is not a valid decimal")]
+    UnableToParse,
+    #[error("DidNotConverge: iteration limit reached before converging")]
+    DidNotConverge,
+    #[error("EmptyInput: input slice is empty")]
+    EmptyInput,
+    #[error("DivisorTooSmall: divisor is below the allowed minimum")]
+    DivisorTooSmall,
 }
 
 impl From<MathError> for ProgramError {
@@ -29,3 +47,22 @@ impl<T> DecodeError<T> for MathError {
         "Math Error"
     }
 }
+
+#[cfg(feature = "std")]
+impl From<MathError> for std::io::Error {
+    fn from(e: MathError) -> Self {
+        std::io::Error::new(std::io::ErrorKind::InvalidData, e.to_string())
+    }
+}
+
+#[cfg(all(test, feature = "std"))]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_io_error() {
+        let e = std::io::Error::from(MathError::DividedByZero);
+        assert_eq!(e.kind(), std::io::ErrorKind::InvalidData);
+        assert_eq!(e.to_string(), "DividedByZero: attempted division by zero");
+    }
+}
@@ -1,10 +1,12 @@
 //! Math for preserving precision
 
+mod amm;
 mod common;
 mod decimal;
 mod error;
 mod rate;
 
+pub use amm::*;
 pub use common::*;
 pub use decimal::*;
 pub use error::*;
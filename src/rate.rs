@@ -11,13 +11,14 @@
 #![allow(clippy::ptr_offset_with_cast)]
 #![allow(clippy::reversed_empty_ranges)]
 #![allow(clippy::manual_range_contains)]
+#![allow(clippy::manual_is_multiple_of)]
 
 use crate::{
     Decimal, MathError, TryAdd, TryDiv, TryMul, TrySub, BIPS_SCALER, HALF_WAD, PERCENT_SCALER,
     SCALE, WAD,
 };
 use solana_program::program_error::ProgramError;
-use std::{convert::TryFrom, fmt};
+use std::{convert::TryFrom, fmt, str::FromStr};
 use uint::construct_uint;
 
 // U128 with 128 bits consisting of 2 x 64-bit words
@@ -90,7 +91,8 @@ impl Rate {
             Rate(Self::wad())
         };
 
-        while exp > 0 {
+        // Stop before squaring a base that no remaining bit would use
+        while exp > 1 {
             exp /= 2;
             base = base.try_mul(base)?;
 
@@ -101,6 +103,164 @@ impl Rate {
 
         Ok(ret)
     }
+
+    /// Convert an annual rate to a per-second rate
+    pub fn per_second(&self, seconds_per_year: u64) -> Result<Self, ProgramError> {
+        self.try_div(seconds_per_year)
+    }
+
+    /// Convert a per-second rate to an annual rate
+    pub fn annualize_per_second(&self, seconds_per_year: u64) -> Result<Self, ProgramError> {
+        self.try_mul(seconds_per_year)
+    }
+
+    /// Calculates 1 - self
+    pub fn complement(&self) -> Result<Self, ProgramError> {
+        Self::one().try_sub(*self)
+    }
+
+    /// Calculates the APY (1 + period_rate)^periods - 1
+    pub fn apy(period_rate: Self, periods: u64) -> Result<Self, ProgramError> {
+        Self::one()
+            .try_add(period_rate)?
+            .try_pow(periods)?
+            .try_sub(Self::one())
+    }
+
+    /// Utilization borrowed / supplied capped at one. Zero supply means
+    /// nothing can be borrowed, so it is reported as zero utilization.
+    pub fn utilization(borrowed: Decimal, supplied: Decimal) -> Result<Self, ProgramError> {
+        if supplied == Decimal::zero() {
+            return Ok(Self::zero());
+        }
+        Self::try_from(borrowed.try_div(supplied)?.min(Decimal::one()))
+    }
+
+    /// Multiply by a decimal, widening the result to a decimal
+    pub fn try_mul_decimal(self, rhs: Decimal) -> Result<Decimal, ProgramError> {
+        Decimal::from(self).try_mul(rhs)
+    }
+
+    /// Parse a rate written as a ratio ("0.05"), a percentage ("5%") or
+    /// basis points ("500bps" or "500bips")
+    pub fn parse_rate_spec(s: &str) -> Result<Self, ProgramError> {
+        let s = s.trim();
+        let (number, divisor) = if let Some(number) = s.strip_suffix('%') {
+            (number, 100)
+        } else if let Some(number) = s.strip_suffix("bps").or_else(|| s.strip_suffix("bips")) {
+            (number, 10_000)
+        } else {
+            (s, 1)
+        };
+        Self::try_from(Decimal::from_str(number.trim())?.try_div(divisor)?)
+    }
+
+    /// Return an OutOfRange error unless lo <= self <= hi
+    pub fn require_in_range(&self, lo: Self, hi: Self) -> Result<(), ProgramError> {
+        if *self < lo || *self > hi {
+            return Err(MathError::OutOfRange.into());
+        }
+        Ok(())
+    }
+
+    /// Check |self - other| <= max(self, other) * rel_tol
+    pub fn approx_eq_rel(&self, other: Self, rel_tol: Rate) -> Result<bool, ProgramError> {
+        Decimal::from(*self).approx_eq_rel(Decimal::from(other), rel_tol)
+    }
+
+    /// Fractional part, self - floor(self)
+    pub fn fract(&self) -> Self {
+        Self(self.0 % Self::wad())
+    }
+
+    /// Integer part, floor(self)
+    pub fn trunc(&self) -> Self {
+        Self(self.0 - self.0 % Self::wad())
+    }
+
+    /// Round half up to the given decimal places, also reporting whether
+    /// any nonzero digits were discarded
+    pub fn round_to(&self, places: u8) -> Result<(Self, bool), ProgramError> {
+        let (rounded, inexact) = Decimal::from(*self).round_to(places)?;
+        Ok((Self::try_from(rounded)?, inexact))
+    }
+
+    /// Convert to the percentage it represents, e.g. 5% to 5
+    pub fn to_decimal_as_percent(&self) -> Result<Decimal, ProgramError> {
+        Decimal::from(*self).try_mul(100u64)
+    }
+
+    /// Divide by u64, rounding the last digit up
+    pub fn try_ceil_div(&self, rhs: u64) -> Result<Self, ProgramError> {
+        if rhs == 0 {
+            return Err(MathError::DividedByZero.into());
+        }
+        let rhs = U128::from(rhs);
+        Ok(Self(
+            self.0
+                .checked_add(rhs - U128::one())
+                .ok_or(MathError::AddOverflow)?
+                / rhs,
+        ))
+    }
+
+    /// Divide by u64, rounding the last digit down
+    pub fn try_floor_div(&self, rhs: u64) -> Result<Self, ProgramError> {
+        self.try_div(rhs)
+    }
+
+    /// Subtract, returning (self - rhs, zero) or (zero, rhs - self) when
+    /// rhs is larger, so the shortfall is reported instead of an error
+    pub fn try_sub_or_deficit(&self, rhs: Self) -> (Self, Self) {
+        if *self >= rhs {
+            (Self(self.0 - rhs.0), Self::zero())
+        } else {
+            (Self::zero(), Self(rhs.0 - self.0))
+        }
+    }
+
+    /// Divide, rounding the last digit half to even
+    pub fn try_div_half_even(&self, rhs: Self) -> Result<Self, ProgramError> {
+        Self::try_from(Decimal::from(*self).try_div_half_even(Decimal::from(rhs))?)
+    }
+
+    /// Smallest value in a slice, None if empty
+    pub fn min_of(values: &[Self]) -> Option<Self> {
+        values.iter().min().copied()
+    }
+
+    /// Largest value in a slice, None if empty
+    pub fn max_of(values: &[Self]) -> Option<Self> {
+        values.iter().max().copied()
+    }
+
+    /// Truncate to n decimal places without rounding
+    pub fn try_truncate_to_decimals(&self, n: u8) -> Result<Self, ProgramError> {
+        Self::try_from(Decimal::from(*self).try_truncate_to_decimals(n)?)
+    }
+
+    /// Clamp to [lo, hi], also reporting whether the value was adjusted
+    pub fn clamp_reporting(&self, lo: Self, hi: Self) -> (Self, bool) {
+        if *self < lo {
+            (lo, true)
+        } else if *self > hi {
+            (hi, true)
+        } else {
+            (*self, false)
+        }
+    }
+
+    /// Remainder of self / rhs
+    pub fn try_rem(&self, rhs: Self) -> Result<Self, ProgramError> {
+        Ok(Self(
+            self.0.checked_rem(rhs.0).ok_or(MathError::DividedByZero)?,
+        ))
+    }
+
+    /// Whether self has no fractional part
+    pub fn is_integer(&self) -> bool {
+        self.fract() == Self::zero()
+    }
 }
 
 impl fmt::Display for Rate {
@@ -191,4 +351,212 @@ mod test {
     fn checked_pow() {
         assert_eq!(Rate::one(), Rate::one().try_pow(u64::MAX).unwrap());
     }
+
+    #[test]
+    fn test_per_second() {
+        let seconds_per_year = 31_536_000;
+        let annual = Rate::from_percent(10);
+        let per_second = annual.per_second(seconds_per_year).unwrap();
+        assert_eq!(per_second, Rate::from_scaled_val(3_170_979_198));
+
+        let annualized = per_second.annualize_per_second(seconds_per_year).unwrap();
+        let error = annual.try_sub(annualized).unwrap();
+        assert!(error < Rate::from_scaled_val(seconds_per_year));
+    }
+
+    #[test]
+    fn test_complement() {
+        assert_eq!(
+            Rate::from_percent(30).complement().unwrap(),
+            Rate::from_percent(70)
+        );
+        assert_eq!(Rate::zero().complement().unwrap(), Rate::one());
+        assert_eq!(
+            Rate::from_percent(101).complement(),
+            Err(MathError::SubUnderflow.into())
+        );
+    }
+
+    #[test]
+    fn test_apy() {
+        let daily_rate = Rate::from_percent(5).try_div(365).unwrap();
+        let apy = Rate::apy(daily_rate, 365).unwrap();
+        let expected = Rate::from_scaled_val(51_267_496_467_462_545);
+        assert!(expected.try_sub(apy).unwrap() < Rate::from_scaled_val(1_000));
+        assert_eq!(Rate::apy(daily_rate, 0).unwrap(), Rate::zero());
+        assert_eq!(
+            Rate::apy(Rate::from_percent(100), 128),
+            Err(MathError::MulOverflow.into())
+        );
+        // 1.05^100 fits even though 1.05^128 would not
+        let apy = Rate::apy(Rate::from_percent(5), 100).unwrap();
+        let expected = Decimal::from_scaled_val(130_501_257_846_303_455_025);
+        assert!(Decimal::from(apy)
+            .approx_eq_rel(expected, Rate::from_scaled_val(1_000))
+            .unwrap());
+        let nineteen = Rate::try_from(Decimal::from(19u64)).unwrap();
+        assert_eq!(Rate::apy(nineteen, 1), Ok(nineteen));
+    }
+
+    #[test]
+    fn test_utilization() {
+        let supplied = Decimal::from(100u64);
+        assert_eq!(
+            Rate::utilization(Decimal::from(25u64), supplied).unwrap(),
+            Rate::from_percent(25)
+        );
+        assert_eq!(Rate::utilization(supplied, supplied).unwrap(), Rate::one());
+        assert_eq!(
+            Rate::utilization(Decimal::from(150u64), supplied).unwrap(),
+            Rate::one()
+        );
+        assert_eq!(
+            Rate::utilization(supplied, Decimal::zero()).unwrap(),
+            Rate::zero()
+        );
+    }
+
+    #[test]
+    fn test_mul_decimal() {
+        let rate = Rate::from_scaled_val(123_456_789_123_456_789);
+        let amounts = [
+            Decimal::zero(),
+            Decimal::from_scaled_val(987_654_321),
+            Decimal::from(u64::MAX),
+        ];
+        for amount in amounts.iter() {
+            assert_eq!(
+                rate.try_mul_decimal(*amount).unwrap(),
+                amount.try_mul(rate).unwrap()
+            );
+        }
+    }
+
+    #[test]
+    fn test_parse_rate_spec() {
+        assert_eq!(Rate::parse_rate_spec("5%").unwrap(), Rate::from_percent(5));
+        assert_eq!(
+            Rate::parse_rate_spec("2.5 %").unwrap(),
+            Rate::from_bips(250)
+        );
+        assert_eq!(
+            Rate::parse_rate_spec("250bps").unwrap(),
+            Rate::from_bips(250)
+        );
+        assert_eq!(
+            Rate::parse_rate_spec("250bips").unwrap(),
+            Rate::from_bips(250)
+        );
+        assert_eq!(
+            Rate::parse_rate_spec("0.05").unwrap(),
+            Rate::from_percent(5)
+        );
+        assert_eq!(
+            Rate::parse_rate_spec("5bp"),
+            Err(MathError::UnableToParse.into())
+        );
+    }
+
+    #[test]
+    fn test_decimal_parity_helpers() {
+        let rate = Rate::from_scaled_val(1_250_000_000_000_000_000);
+        assert!(rate
+            .require_in_range(Rate::one(), Rate::from_percent(200))
+            .is_ok());
+        assert_eq!(
+            rate.require_in_range(Rate::zero(), Rate::one()),
+            Err(MathError::OutOfRange.into())
+        );
+        assert!(rate
+            .approx_eq_rel(
+                Rate::from_scaled_val(1_250_000_000_000_000_001),
+                Rate::from_bips(1)
+            )
+            .unwrap());
+        assert!(!rate.approx_eq_rel(Rate::one(), Rate::from_bips(1)).unwrap());
+        assert_eq!(rate.fract(), Rate::from_percent(25));
+        assert_eq!(rate.trunc(), Rate::one());
+        assert_eq!(
+            rate.round_to(1).unwrap(),
+            (Rate::from_scaled_val(1_300_000_000_000_000_000), true)
+        );
+        assert_eq!(rate.round_to(2).unwrap(), (rate, false));
+
+        let ten = Rate::from_scaled_val(10);
+        assert_eq!(ten.try_ceil_div(3), Ok(Rate::from_scaled_val(4)));
+        assert_eq!(ten.try_floor_div(3), Ok(Rate::from_scaled_val(3)));
+        assert_eq!(ten.try_ceil_div(0), Err(MathError::DividedByZero.into()));
+
+        let small = Rate::from_percent(5);
+        let large = Rate::from_percent(8);
+        let gap = Rate::from_percent(3);
+        assert_eq!(large.try_sub_or_deficit(small), (gap, Rate::zero()));
+        assert_eq!(small.try_sub_or_deficit(large), (Rate::zero(), gap));
+        assert_eq!(
+            small.try_sub_or_deficit(small),
+            (Rate::zero(), Rate::zero())
+        );
+
+        let third = Rate::from_scaled_val(2).try_div_half_even(Rate::from_scaled_val(3 * WAD));
+        assert_eq!(third, Ok(Rate::from_scaled_val(1)));
+        let half = Rate::from_scaled_val(1).try_div_half_even(Rate::from_scaled_val(2 * WAD));
+        assert_eq!(half, Ok(Rate::zero()));
+
+        let values = [Rate::from_percent(5), Rate::zero(), Rate::one()];
+        assert_eq!(Rate::min_of(&values), Some(Rate::zero()));
+        assert_eq!(Rate::max_of(&values), Some(Rate::one()));
+        assert_eq!(Rate::min_of(&[]), None);
+        assert_eq!(Rate::max_of(&[]), None);
+
+        assert_eq!(
+            Rate::from_scaled_val(123_456_789_000_000_000).try_truncate_to_decimals(3),
+            Ok(Rate::from_scaled_val(123_000_000_000_000_000))
+        );
+        assert_eq!(
+            rate.try_truncate_to_decimals(19),
+            Err(MathError::OutOfRange.into())
+        );
+
+        let (lo, hi) = (Rate::from_percent(1), Rate::from_percent(10));
+        assert_eq!(small.clamp_reporting(lo, hi), (small, false));
+        assert_eq!(Rate::zero().clamp_reporting(lo, hi), (lo, true));
+        assert_eq!(Rate::one().clamp_reporting(lo, hi), (hi, true));
+
+        assert_eq!(large.try_rem(small), Ok(gap));
+        assert_eq!(
+            large.try_rem(Rate::zero()),
+            Err(MathError::DividedByZero.into())
+        );
+        assert!(rate.trunc().is_integer());
+        assert!(!rate.is_integer());
+    }
+
+    #[test]
+    fn test_default() {
+        assert_eq!(Rate::default(), Rate::zero());
+    }
+
+    #[test]
+    fn test_sub_underflow() {
+        assert_eq!(
+            Rate::zero().try_sub(Rate::from_percent(1)),
+            Err(MathError::SubUnderflow.into())
+        );
+    }
+
+    #[test]
+    fn test_to_decimal_as_percent() {
+        assert_eq!(
+            Rate::from_percent(7).to_decimal_as_percent(),
+            Ok(Decimal::from(7u64))
+        );
+        assert_eq!(
+            Rate::from_bips(500).to_decimal_as_percent(),
+            Ok(Decimal::from(5u64))
+        );
+        assert_eq!(
+            Rate::from_bips(1).to_decimal_as_percent(),
+            Ok(Decimal::from_percent(1))
+        );
+    }
 }